@@ -0,0 +1,124 @@
+use geo::{BooleanOps, Coord, Line, LineString, MultiPolygon, Point, Polygon};
+
+// sides of the polygon standing in for a circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+pub fn circle(centre: &Point<f64>, radius: f64) -> Polygon<f64> {
+    let exterior: Vec<Coord> = (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            // half a step round, so no corner lands on the corner of a widened line
+            let angle = (i as f64 + 0.5) / CIRCLE_SEGMENTS as f64 * std::f64::consts::TAU;
+            Coord {
+                x: centre.x() + radius * angle.cos(),
+                y: centre.y() + radius * angle.sin(),
+            }
+        })
+        .collect();
+    Polygon::new(LineString(exterior), vec![])
+}
+
+// rectangle either side of a line, None for a line of no length
+fn widen(line: Line<f64>, distance: f64) -> Option<Polygon<f64>> {
+    let delta = line.delta();
+    let length = delta.x.hypot(delta.y);
+    if length == 0.0 {
+        return None;
+    }
+    let normal = Coord {
+        x: -delta.y / length * distance,
+        y: delta.x / length * distance,
+    };
+    Some(Polygon::new(
+        LineString(vec![
+            line.start + normal,
+            line.end + normal,
+            line.end - normal,
+            line.start - normal,
+        ]),
+        vec![],
+    ))
+}
+
+// halving keeps each union small, where adding one polygon at a time grows a single large one
+fn union_all(polygons: &[Polygon<f64>]) -> MultiPolygon<f64> {
+    match polygons {
+        [] => MultiPolygon(vec![]),
+        [polygon] => MultiPolygon(vec![polygon.clone()]),
+        _ => {
+            let (left, right) = polygons.split_at(polygons.len() / 2);
+            union_all(left).union(&union_all(right))
+        }
+    }
+}
+
+// everything within a distance of the exterior or a hole of the polygons
+fn boundary_band(polygons: &MultiPolygon<f64>, distance: f64) -> MultiPolygon<f64> {
+    let rings = polygons
+        .iter()
+        .flat_map(|polygon| std::iter::once(polygon.exterior()).chain(polygon.interiors()));
+    let pieces: Vec<Polygon<f64>> = rings
+        .flat_map(|ring| {
+            let sides = ring.lines().filter_map(|line| widen(line, distance));
+            let corners = ring.points().map(|point| circle(&point, distance));
+            sides.chain(corners).collect::<Vec<Polygon<f64>>>()
+        })
+        .collect();
+    union_all(&pieces)
+}
+
+// shrink polygons by a distance, so the edges stay that far from the original boundary
+pub fn inward_buffer(polygons: &MultiPolygon<f64>, distance: f64) -> MultiPolygon<f64> {
+    if distance <= 0.0 {
+        return polygons.clone();
+    }
+    polygons.difference(&boundary_band(polygons, distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{coord, Area, Contains, Rect};
+
+    fn square(min: f64, max: f64) -> Polygon<f64> {
+        Rect::new(coord! { x: min, y: min }, coord! { x: max, y: max }).to_polygon()
+    }
+
+    #[test]
+    fn inward_buffer_keeps_clear_of_the_edges_and_holes() {
+        let loe = MultiPolygon(vec![square(0.0, 100.0)]);
+        let buffered = inward_buffer(&loe, 5.0);
+        assert!((buffered.unsigned_area() - 90.0 * 90.0).abs() < 1e-6);
+        assert!(buffered.contains(&Point::new(6.0, 50.0)));
+        assert!(!buffered.contains(&Point::new(4.0, 50.0)));
+        assert_eq!(inward_buffer(&loe, 0.0), loe);
+
+        // a hole grows by the buffer, with rounded corners
+        let holed = MultiPolygon(vec![Polygon::new(
+            square(0.0, 100.0).exterior().clone(),
+            vec![square(40.0, 60.0).exterior().clone()],
+        )]);
+        let buffered = inward_buffer(&holed, 5.0);
+        assert!(!buffered.contains(&Point::new(50.0, 64.0)));
+        assert!(buffered.contains(&Point::new(50.0, 66.0)));
+        let hole_area = 20.0 * 20.0 + 4.0 * 20.0 * 5.0 + std::f64::consts::PI * 25.0;
+        assert!((buffered.unsigned_area() - (90.0 * 90.0 - hole_area)).abs() < 1.0);
+    }
+
+    #[test]
+    fn circle_is_close_to_its_radius() {
+        let circle = circle(&Point::new(3.0, 4.0), 2.0);
+        assert_eq!(circle.exterior().0.len(), CIRCLE_SEGMENTS + 1);
+        let area = std::f64::consts::PI * 4.0;
+        assert!((circle.unsigned_area() - area).abs() / area < 0.01);
+    }
+
+    #[test]
+    fn excavation_buffers_the_loe_before_taking_out_exclusions() {
+        let loe = MultiPolygon(vec![square(0.0, 100.0)]);
+        let exclusions = MultiPolygon(vec![square(40.0, 60.0)]);
+        let excavation =
+            crate::Excavation::new(&loe, &exclusions, 5.0, crate::CoverageBasis::Available);
+        assert!((excavation.available.unsigned_area() - (90.0 * 90.0 - 20.0 * 20.0)).abs() < 1e-6);
+        assert!((excavation.coverage_area - excavation.available.unsigned_area()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{anyhow, Result};
+use fs_err::File;
+use serde::Deserialize;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+// a site grouped by the preprocessing, with the LOEs to test and any known context
+#[derive(Debug, Clone, Deserialize)]
+pub struct Site {
+    pub name: String,
+    #[serde(default)]
+    pub loes: Vec<String>, // found by scanning the site folder when empty
+    pub period: Option<String>,
+    pub geology: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    data_root: Option<PathBuf>, // relative to the manifest, defaults to its folder
+    sites: Vec<Site>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SiteCatalogue {
+    pub data_root: PathBuf,
+    pub sites: Vec<Site>,
+}
+
+impl SiteCatalogue {
+    // use the manifest in the data root if there is one, otherwise scan the data root
+    pub fn load(data_root: impl AsRef<Path>) -> Result<Self> {
+        let manifest_path = data_root.as_ref().join(MANIFEST_FILE_NAME);
+        if manifest_path.exists() {
+            Self::from_manifest(manifest_path)
+        } else {
+            Self::discover(data_root)
+        }
+    }
+
+    // every folder in the data root is a site, every folder in a site with an LOE is a location
+    pub fn discover(data_root: impl AsRef<Path>) -> Result<Self> {
+        let data_root = data_root.as_ref().to_path_buf();
+        let sites = list_folders(&data_root)?
+            .into_iter()
+            .map(|name| {
+                let loes = discover_loes(&data_root.join(&name))?;
+                Ok(Site {
+                    name,
+                    loes,
+                    period: None,
+                    geology: None,
+                })
+            })
+            .collect::<Result<Vec<Site>>>()?
+            .into_iter()
+            .filter(|site| !site.loes.is_empty())
+            .collect();
+        Ok(SiteCatalogue { data_root, sites })
+    }
+
+    // sites listed in a manifest, so new datasets can be added without recompiling
+    pub fn from_manifest(manifest_path: impl AsRef<Path>) -> Result<Self> {
+        let manifest_path = manifest_path.as_ref();
+        let manifest_folder = manifest_path
+            .parent()
+            .ok_or_else(|| anyhow!("Manifest {:?} has no parent folder", manifest_path))?;
+        let reader = BufReader::new(File::open(manifest_path)?);
+        let manifest: Manifest = serde_json::from_reader(reader)?;
+        let data_root = match manifest.data_root {
+            Some(data_root) => manifest_folder.join(data_root),
+            None => manifest_folder.to_path_buf(),
+        };
+        let sites = manifest
+            .sites
+            .into_iter()
+            .map(|mut site| {
+                if site.loes.is_empty() {
+                    site.loes = discover_loes(&data_root.join(&site.name))?;
+                }
+                Ok(site)
+            })
+            .collect::<Result<Vec<Site>>>()?;
+        Ok(SiteCatalogue { data_root, sites })
+    }
+
+    pub fn select_sites(self, site_names: &[String]) -> Self {
+        SiteCatalogue {
+            sites: self
+                .sites
+                .into_iter()
+                .filter(|site| site_names.contains(&site.name))
+                .collect(),
+            ..self
+        }
+    }
+
+    pub fn site(&self, site_name: &str) -> Option<&Site> {
+        self.sites.iter().find(|site| site.name == site_name)
+    }
+
+    pub fn location_path(&self, site_name: &str, loe_i: &str) -> PathBuf {
+        self.data_root.join(site_name).join(loe_i)
+    }
+}
+
+fn list_folders(path: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs_err::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && !name.starts_with('.') {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn discover_loes(site_path: &Path) -> Result<Vec<String>> {
+    let mut loes: Vec<String> = list_folders(site_path)?
+        .into_iter()
+        .filter(|loe_i| site_path.join(loe_i).join("loe.geojson").exists())
+        .collect();
+    // LOEs are numbered by the preprocessing, so sort 2 before 10
+    loes.sort_by_key(|loe_i| (loe_i.parse::<usize>().unwrap_or(usize::MAX), loe_i.clone()));
+    Ok(loes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a data root unique to the test, with an LOE in each of the given site folders
+    fn data_root(test: &str, loes: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("catalogue_{}_{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (site, loe_i) in loes {
+            let loe_path = root.join(site).join(loe_i);
+            std::fs::create_dir_all(&loe_path).unwrap();
+            std::fs::write(loe_path.join("loe.geojson"), "{}").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn discover_finds_numbered_loes_and_skips_empty_sites() {
+        let root = data_root("discover", &[("b", "10"), ("b", "2"), ("a", "0")]);
+        std::fs::create_dir_all(root.join("b").join("no_loe")).unwrap();
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::create_dir_all(root.join(".hidden").join("0")).unwrap();
+
+        let catalogue = SiteCatalogue::load(&root).unwrap();
+        let names: Vec<&str> = catalogue
+            .sites
+            .iter()
+            .map(|site| site.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(catalogue.site("b").unwrap().loes, vec!["2", "10"]);
+        assert!(catalogue.site("empty").is_none());
+        assert_eq!(catalogue.location_path("b", "2"), root.join("b").join("2"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn manifest_gives_context_and_discovers_unlisted_loes() {
+        let root = data_root(
+            "manifest",
+            &[("data/a", "0"), ("data/a", "1"), ("data/b", "3")],
+        );
+        std::fs::write(
+            root.join(MANIFEST_FILE_NAME),
+            r#"{
+                "data_root": "data",
+                "sites": [
+                    {"name": "a", "period": "Roman", "geology": "clay"},
+                    {"name": "b", "loes": ["3"]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let catalogue = SiteCatalogue::load(&root).unwrap();
+        assert_eq!(catalogue.data_root, root.join("data"));
+        let site = catalogue.site("a").unwrap();
+        assert_eq!(site.loes, vec!["0", "1"]);
+        assert_eq!(site.period.as_deref(), Some("Roman"));
+        assert_eq!(site.geology.as_deref(), Some("clay"));
+        assert_eq!(catalogue.site("b").unwrap().period, None);
+
+        let selected = catalogue.select_sites(&["b".to_string()]);
+        assert_eq!(selected.sites.len(), 1);
+        assert_eq!(selected.sites[0].loes, vec!["3"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,430 @@
+use geo::line_intersection::{line_intersection, LineIntersection};
+use geo::{
+    Area, Closest, ClosestPoint, Coord, EuclideanDistance, Line, LineString, MultiPolygon, Point,
+    Polygon, Simplify,
+};
+use rayon::prelude::*;
+use trenching_optimisation::{
+    CoverageFailure, Degree, Distribution, Excavation, LayoutPlacement, Offset, Percentage,
+    Structure, TrenchConfig, TrenchLayout,
+};
+
+use crate::trench;
+
+// points each side of the LOE is resampled to when finding the centre line between them
+const BANK_SAMPLES: usize = 200;
+
+// the centre line found from the LOE is simplified to within this fraction of the corridor width
+const SIMPLIFY_FRACTION: f64 = 0.05;
+
+// an LOE shorter than this many times its width is not a corridor, and the line between its
+// sides only wanders, so a straight line between the ends is used instead
+const MIN_ELONGATION: f64 = 3.0;
+
+// how far a mitred corner of a curve may be pushed out, as a multiple of the offset
+const MAX_MITRE: f64 = 4.0;
+
+// the centre line of a corridor, extended straight past both ends so trenches reach them,
+// walked by the distance along it
+struct Alignment {
+    coords: Vec<Coord>,
+    chainages: Vec<f64>, // distance along the alignment to each coord
+    half_width: f64,     // farthest the corridor reaches from the alignment
+}
+
+// a layout for each offset along and across the alignment, which is not rotated
+pub fn create_layouts(config: &TrenchConfig, excavation: &Excavation) -> Option<Vec<TrenchLayout>> {
+    let alignment = Alignment::of_excavation(config, excavation)?;
+    match config.distribution {
+        Distribution::Spacing(spacing) => Some(
+            config
+                .get_offsets()
+                .into_par_iter()
+                .map(|offset| create_layout(config, excavation, &alignment, spacing, offset))
+                .collect(),
+        ),
+        Distribution::Coverage(coverage) => {
+            let results = config
+                .get_offsets()
+                .into_par_iter()
+                .map(|offset| {
+                    let result = solve_coverage(config, excavation, &alignment, coverage, offset);
+                    (0, offset, result)
+                })
+                .collect();
+            trench::collect_solutions(config, coverage, results)
+        }
+        Distribution::Cost(_) | Distribution::Count(_) => create_layouts(
+            &trench::with_equivalent_coverage(config, excavation),
+            excavation,
+        ),
+    }
+}
+
+pub fn create_single_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    offset: Offset,
+) -> Result<TrenchLayout, CoverageFailure> {
+    let alignment =
+        Alignment::of_excavation(config, excavation).ok_or(CoverageFailure::NothingAvailable)?;
+    match config.distribution {
+        Distribution::Spacing(spacing) => Ok(create_layout(
+            config, excavation, &alignment, spacing, offset,
+        )),
+        Distribution::Coverage(coverage) => {
+            solve_coverage(config, excavation, &alignment, coverage, offset)
+                .map(trench::solution_layout)
+        }
+        Distribution::Cost(_) | Distribution::Count(_) => create_single_layout(
+            &trench::with_equivalent_coverage(config, excavation),
+            excavation,
+            offset,
+        ),
+    }
+}
+
+fn create_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    alignment: &Alignment,
+    spacing: f64,
+    offset: Offset,
+) -> TrenchLayout {
+    let trench_pattern = alignment.pattern(config, spacing, offset);
+    trench::placed_layout(
+        trench::fit_to_excavation(&trench_pattern, excavation, config),
+        excavation,
+        config,
+        LayoutPlacement {
+            rotation: Degree(0.0),
+            spacing,
+            offset,
+        },
+    )
+}
+
+fn solve_coverage(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    alignment: &Alignment,
+    coverage: Percentage,
+    offset: Offset,
+) -> Result<trench::CoverageSolution, CoverageFailure> {
+    let estimated_spacing =
+        trench::estimate_spacing(config, &excavation.coverage_of_available(coverage));
+    trench::solve_coverage(
+        config,
+        excavation,
+        alignment.length() * 2.0,
+        estimated_spacing,
+        coverage.0,
+        0,
+        offset,
+        |config, spacing| alignment.pattern(config, spacing, offset),
+    )
+}
+
+impl Alignment {
+    // the supplied centre line, or one found between the sides of the largest part of the LOE
+    fn of_excavation(config: &TrenchConfig, excavation: &Excavation) -> Option<Self> {
+        let line = match &excavation.centre_line {
+            Some(line) => line.clone(),
+            None => {
+                let largest = excavation
+                    .available
+                    .iter()
+                    .max_by(|a, b| a.unsigned_area().total_cmp(&b.unsigned_area()))?;
+                find_centre_line(largest)?
+            }
+        };
+        let half_width = excavation
+            .available
+            .iter()
+            .flat_map(|polygon| polygon.exterior().points())
+            .map(|point| point.euclidean_distance(&line))
+            .fold(0.0, f64::max);
+        let trench_length = match config.structure {
+            Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => 0.0,
+            Structure::Array(rectangle, _) => rectangle.length,
+        };
+        Self::new(line, half_width, half_width * 2.0 + trench_length)
+    }
+
+    // None for a line of no length
+    fn new(line: LineString, half_width: f64, extension: f64) -> Option<Self> {
+        let mut coords: Vec<Coord> = line.0;
+        coords.dedup();
+        if coords.len() < 2 {
+            return None;
+        }
+        let start_direction = unit(coords[0] - coords[1]);
+        let end_direction = unit(coords[coords.len() - 1] - coords[coords.len() - 2]);
+        // the ends are moved rather than added to, as the sweep trips over collinear points
+        let last = coords.len() - 1;
+        coords[0] = coords[0] + start_direction * extension;
+        coords[last] = coords[last] + end_direction * extension;
+        let chainages = chainages(&coords);
+        Some(Alignment {
+            coords,
+            chainages,
+            half_width,
+        })
+    }
+
+    fn length(&self) -> f64 {
+        self.chainages[self.chainages.len() - 1]
+    }
+
+    // point and direction at a distance along the alignment, carrying on straight past the ends
+    fn station(&self, chainage: f64) -> (Coord, Coord) {
+        let i = self
+            .chainages
+            .partition_point(|&c| c <= chainage)
+            .clamp(1, self.coords.len() - 1);
+        let direction = unit(self.coords[i] - self.coords[i - 1]);
+        (
+            self.coords[i - 1] + direction * (chainage - self.chainages[i - 1]),
+            direction,
+        )
+    }
+
+    // the alignment moved a distance to its left, negative to its right, mitred at each bend,
+    // with the loops cut out where the inside of a bend is tighter than the distance
+    fn offset(&self, distance: f64) -> Vec<Coord> {
+        let last = self.coords.len() - 1;
+        let mut offset: Vec<Coord> = (0..=last)
+            .map(|i| {
+                let before = left_normal(self.coords[i.max(1)] - self.coords[i.max(1) - 1]);
+                let after =
+                    left_normal(self.coords[i.min(last - 1) + 1] - self.coords[i.min(last - 1)]);
+                let mitre = unit(before + after);
+                let stretch = 1.0 / (mitre.x * before.x + mitre.y * before.y).max(1.0 / MAX_MITRE);
+                self.coords[i] + mitre * (distance * stretch)
+            })
+            .collect();
+        offset.dedup();
+        remove_loops(&offset)
+    }
+
+    // continuous trenches follow the alignment at each row across it, array trenches sit on the
+    // lattice laid along it and are turned with it, an angle of 0 running along the alignment
+    fn pattern(&self, config: &TrenchConfig, spacing: f64, offset: Offset) -> MultiPolygon {
+        match config.structure {
+            Structure::Parallel(line) => {
+                let rows = (self.half_width / spacing).ceil() as i32 + 1;
+                MultiPolygon(
+                    (-rows..rows + 1)
+                        .map(|row| {
+                            let distance = (row as f64 + offset.y) * spacing;
+                            let mut exterior = self.offset(distance + line.width / 2.0);
+                            exterior
+                                .extend(self.offset(distance - line.width / 2.0).into_iter().rev());
+                            exterior.push(exterior[0]);
+                            Polygon::new(LineString(exterior), vec![])
+                        })
+                        .collect(),
+                )
+            }
+            Structure::Array(rectangle, array_config) => {
+                let lattice = array_config.lattice;
+                let rows = (self.half_width / (spacing * lattice.row_spacing())).ceil() as i32 + 1;
+                let columns = (self.length() / spacing).ceil() as i32 + 1;
+                MultiPolygon(
+                    (-1..columns + 1)
+                        .into_par_iter()
+                        .flat_map(|column| {
+                            (-rows..rows + 1)
+                                .filter_map(|row| {
+                                    let angle = array_config.angle_of_cell(column, row)?;
+                                    let (x, y) = lattice.position(column, row);
+                                    let (point, direction) = self.station((x + offset.x) * spacing);
+                                    let centre =
+                                        point + left_normal(direction) * ((y + offset.y) * spacing);
+                                    let heading =
+                                        direction.y.atan2(direction.x).to_degrees() - 90.0;
+                                    Some(trench::plot_trench(
+                                        Point::from(centre),
+                                        rectangle.width,
+                                        rectangle.length,
+                                        Degree(heading + angle.0),
+                                    ))
+                                })
+                                .collect::<Vec<Polygon>>()
+                        })
+                        .collect(),
+                )
+            }
+            // laid around their focus by radial before reaching an alignment
+            Structure::Spokes(_) | Structure::Rings(_) => MultiPolygon(vec![]),
+        }
+    }
+}
+
+// the line midway between the two sides of a long polygon, which run between the two points of
+// its exterior farthest apart, from each point of one side to the closest of the other, trimmed
+// where the ends pull it towards a corner and simplified to drop the wobble of the sides, or
+// straightened when the LOE is not long enough to be a corridor
+fn find_centre_line(polygon: &Polygon) -> Option<LineString> {
+    let ring = &polygon.exterior().0;
+    let coords = &ring[..ring.len().saturating_sub(1)];
+    if coords.len() < 3 {
+        return None;
+    }
+    let (start, end) = (0..coords.len())
+        .flat_map(|i| (i + 1..coords.len()).map(move |j| (i, j)))
+        .max_by(|&(a, b), &(c, d)| {
+            distance(coords[a], coords[b]).total_cmp(&distance(coords[c], coords[d]))
+        })?;
+    let side: Vec<Coord> = coords[start..=end].to_vec();
+    let mut other_side: Vec<Coord> = coords[end..]
+        .iter()
+        .chain(&coords[..=start])
+        .cloned()
+        .collect();
+    other_side.reverse();
+    let other_side = LineString(other_side);
+    let middle: Vec<Coord> = resample(&side, BANK_SAMPLES)
+        .into_iter()
+        .filter_map(
+            |coord| match other_side.closest_point(&Point::from(coord)) {
+                Closest::Intersection(point) | Closest::SinglePoint(point) => {
+                    Some((coord + point.0) / 2.0)
+                }
+                Closest::Indeterminate => None,
+            },
+        )
+        .collect();
+    if middle.len() < 2 {
+        return None;
+    }
+    let middle_chainages = chainages(&middle);
+    let length = middle_chainages[middle_chainages.len() - 1];
+    let width = polygon.unsigned_area() / length;
+    let trimmed: Vec<Coord> = middle
+        .iter()
+        .zip(&middle_chainages)
+        .filter(|(_, &chainage)| chainage >= width / 2.0 && chainage <= length - width / 2.0)
+        .map(|(coord, _)| *coord)
+        .collect();
+    let line = if trimmed.len() >= 2 { trimmed } else { middle };
+    let extent = distance(coords[start], coords[end]);
+    if extent < polygon.unsigned_area() / extent * MIN_ELONGATION {
+        return Some(LineString(vec![line[0], line[line.len() - 1]]));
+    }
+    Some(LineString(line).simplify(&(width * SIMPLIFY_FRACTION)))
+}
+
+// a line that crosses itself goes straight from the first crossing to where it comes out of the
+// last loop
+fn remove_loops(coords: &[Coord]) -> Vec<Coord> {
+    let lines: Vec<Line> = coords
+        .windows(2)
+        .map(|pair| Line::new(pair[0], pair[1]))
+        .collect();
+    let mut kept = vec![coords[0]];
+    let mut i = 0;
+    while i < lines.len() {
+        let crossing =
+            (i + 2..lines.len())
+                .rev()
+                .find_map(|j| match line_intersection(lines[i], lines[j]) {
+                    Some(LineIntersection::SinglePoint { intersection, .. }) => {
+                        Some((j, intersection))
+                    }
+                    _ => None,
+                });
+        match crossing {
+            Some((j, intersection)) => {
+                kept.push(intersection);
+                i = j;
+            }
+            None => {
+                kept.push(lines[i].end);
+                i += 1;
+            }
+        }
+    }
+    kept
+}
+
+// points evenly spaced along a line, both ends included
+fn resample(coords: &[Coord], samples: usize) -> Vec<Coord> {
+    let chainages = chainages(coords);
+    let length = chainages[chainages.len() - 1];
+    (0..=samples)
+        .map(|k| {
+            let chainage = length * k as f64 / samples as f64;
+            let i = chainages
+                .partition_point(|&c| c < chainage)
+                .clamp(1, coords.len() - 1);
+            let segment = chainages[i] - chainages[i - 1];
+            if segment == 0.0 {
+                coords[i]
+            } else {
+                coords[i - 1]
+                    + (coords[i] - coords[i - 1]) * ((chainage - chainages[i - 1]) / segment)
+            }
+        })
+        .collect()
+}
+
+fn chainages(coords: &[Coord]) -> Vec<f64> {
+    std::iter::once(0.0)
+        .chain(coords.windows(2).scan(0.0, |chainage, pair| {
+            *chainage += distance(pair[0], pair[1]);
+            Some(*chainage)
+        }))
+        .collect()
+}
+
+fn distance(a: Coord, b: Coord) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+fn unit(vector: Coord) -> Coord {
+    let length = vector.x.hypot(vector.y);
+    if length == 0.0 {
+        vector
+    } else {
+        vector / length
+    }
+}
+
+fn left_normal(vector: Coord) -> Coord {
+    unit(Coord {
+        x: -vector.y,
+        y: vector.x,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords(points: &[(f64, f64)]) -> Vec<Coord> {
+        points.iter().map(|&(x, y)| Coord { x, y }).collect()
+    }
+
+    #[test]
+    fn remove_loops_leaves_a_line_that_does_not_cross_itself() {
+        let line = coords(&[(0.0, 0.0), (10.0, 0.0), (10.0, 5.0), (20.0, 5.0)]);
+        assert_eq!(remove_loops(&line), line);
+    }
+
+    #[test]
+    fn remove_loops_goes_straight_across_a_loop() {
+        // round a square and back across the first segment
+        let line = coords(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 5.0),
+            (5.0, 5.0),
+            (5.0, -5.0),
+        ]);
+        assert_eq!(
+            remove_loops(&line),
+            coords(&[(0.0, 0.0), (5.0, 0.0), (5.0, -5.0)])
+        );
+    }
+}
@@ -0,0 +1,551 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use trenching_optimisation::array::Lattice;
+use trenching_optimisation::cost::CostModel;
+use trenching_optimisation::detection::{DetectionRule, DiscoveryRule};
+use trenching_optimisation::{
+    ClippingPolicy, CoverageBasis, CoverageSolver, Distribution, Orientation, Percentage,
+    Placement, TrenchType,
+};
+
+// grouped output of the preprocessing, add a manifest.json here to choose sites and LOEs
+pub const DATA_ROOT: &str = "../data/grouped_by_loe";
+
+#[derive(Debug, Parser)]
+#[command(about = "Generate, evaluate and compare archaeological trenching layouts")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Write a trench layout for a limit of excavation to GeoJSON
+    Generate {
+        /// GeoJSON Feature with the limit of excavation polygon
+        #[arg(long)]
+        loe: PathBuf,
+        #[arg(long)]
+        output: PathBuf,
+        #[command(flatten)]
+        trench: TrenchArgs,
+        #[command(flatten)]
+        lattice: LatticeArgs,
+        #[command(flatten)]
+        distribution: DistributionArgs,
+        /// GeoJSON of polygons no trench may go in, such as buried services
+        #[arg(long)]
+        exclusions: Option<PathBuf>,
+        /// GeoJSON line of a road or pipeline for centre-line placement, found from the LOE
+        /// when left out
+        #[arg(long)]
+        centre_line: Option<PathBuf>,
+        /// GeoJSON point spokes radiate from and rings are drawn around, the LOE centroid when
+        /// left out
+        #[arg(long)]
+        focus: Option<PathBuf>,
+        /// What a percentage coverage is of once exclusion zones and the boundary buffer are
+        /// taken out of the LOE
+        #[arg(long, value_enum, default_value_t = CoverageBasisName::Available)]
+        coverage_of: CoverageBasisName,
+        /// Meters trenches are kept inside the edge of the LOE and any holes in it
+        #[arg(long, default_value_t = 0.0)]
+        boundary_buffer: f64,
+        #[command(flatten)]
+        clipping: ClippingArgs,
+        #[command(flatten)]
+        solver: SolverArgs,
+        #[command(flatten)]
+        cost: CostArgs,
+        #[command(flatten)]
+        placement: PlacementArgs,
+        #[command(flatten)]
+        orientation: OrientationArgs,
+        /// Degrees to rotate the pattern around the LOE centroid, unless the orientation fixes it
+        #[arg(long, default_value_t = 0)]
+        rotation: i32,
+        /// Shift of the pattern in x as a fraction of the spacing
+        #[arg(long, default_value_t = 0.0)]
+        x_offset: f64,
+        /// Shift of the pattern in y as a fraction of the spacing
+        #[arg(long, default_value_t = 0.0)]
+        y_offset: f64,
+    },
+    /// Count the features in a GeoJSON file hit by a trench layout
+    Evaluate {
+        /// GeoJSON of trench polygons, such as the output of generate
+        #[arg(long)]
+        layout: PathBuf,
+        #[arg(long)]
+        features: PathBuf,
+        #[arg(long)]
+        layer: Option<String>,
+        #[command(flatten)]
+        detection: DetectionArgs,
+        #[command(flatten)]
+        discovery: DiscoveryArgs,
+        /// Write whether each feature was hit, with its layer, type and area, to CSV
+        #[arg(long)]
+        detections: Option<PathBuf>,
+    },
+    /// Write the corners and centre lines of each trench in a layout to CSV for setting out
+    SettingOut {
+        /// GeoJSON of trench polygons, such as the output of generate
+        #[arg(long)]
+        layout: PathBuf,
+        #[arg(long)]
+        output: PathBuf,
+        /// trenches gives a row per trench, survey-points a named point per row
+        #[arg(long, value_enum, default_value_t = SettingOutFormatName::Trenches)]
+        format: SettingOutFormatName,
+    },
+    /// Test every combination of trench types and distributions on the site catalogue
+    Benchmark {
+        #[command(flatten)]
+        sites: SiteArgs,
+        /// Run on a single LOE of each site rather than all of them
+        #[arg(long)]
+        loe: Option<String>,
+        /// continuous, parallel_array, standard_grid, test_pits, herringbone, spokes or rings, may be
+        /// repeated
+        #[arg(long = "type", required = true)]
+        trench_types: Vec<TrenchType>,
+        #[arg(long, default_value_t = 2.0)]
+        width: f64,
+        #[arg(long, default_value_t = 30.0)]
+        length: f64,
+        #[command(flatten)]
+        lattice: LatticeArgs,
+        /// Spacings in meters, may be repeated
+        #[arg(
+            long = "spacing",
+            required_unless_present_any = ["coverages", "budgets", "counts"]
+        )]
+        spacings: Vec<f64>,
+        /// Percentage coverages, may be repeated
+        #[arg(long = "coverage")]
+        coverages: Vec<f64>,
+        /// Budgets, dug as the coverage each pays for at every LOE, may be repeated
+        #[arg(long = "budget")]
+        budgets: Vec<f64>,
+        /// Numbers of trenches, may be repeated
+        #[arg(long = "trenches")]
+        counts: Vec<usize>,
+        /// What a percentage coverage is of once exclusion zones and the boundary buffer are
+        /// taken out of the LOE
+        #[arg(long, value_enum, default_value_t = CoverageBasisName::Available)]
+        coverage_of: CoverageBasisName,
+        /// Meters trenches are kept inside the edge of the LOE and any holes in it
+        #[arg(long, default_value_t = 0.0)]
+        boundary_buffer: f64,
+        #[command(flatten)]
+        clipping: ClippingArgs,
+        #[command(flatten)]
+        solver: SolverArgs,
+        #[command(flatten)]
+        cost: CostArgs,
+        #[command(flatten)]
+        placement: PlacementArgs,
+        #[command(flatten)]
+        orientation: OrientationArgs,
+        /// Shifts of each pattern tested in x and y, 1 keeps the pattern on the LOE centroid
+        #[arg(long, default_value_t = 1)]
+        offset_steps: i32,
+        #[command(flatten)]
+        detection: DetectionArgs,
+        #[command(flatten)]
+        discovery: DiscoveryArgs,
+        /// Write whether each feature was hit by each layout, with its layer, type and area, to CSV
+        #[arg(long)]
+        detections: Option<PathBuf>,
+        /// Write the spread of the percentage found across the layouts at each LOE to CSV
+        #[arg(long)]
+        statistics: Option<PathBuf>,
+    },
+    /// Run every combination listed in a TOML or JSON experiment file and save a CSV of results
+    Experiment {
+        /// Experiment file, see README.md for the format
+        experiment: PathBuf,
+        /// Results CSV, overrides the output in the experiment file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Search for the layout that finds the most features within a coverage budget
+    Optimise {
+        #[command(flatten)]
+        sites: SiteArgs,
+        #[arg(long)]
+        loe: String,
+        #[command(flatten)]
+        trench: TrenchArgs,
+        #[command(flatten)]
+        lattice: LatticeArgs,
+        /// Percentage coverage budget
+        #[arg(long)]
+        coverage: f64,
+        /// What a percentage coverage is of once exclusion zones and the boundary buffer are
+        /// taken out of the LOE
+        #[arg(long, value_enum, default_value_t = CoverageBasisName::Available)]
+        coverage_of: CoverageBasisName,
+        /// Meters trenches are kept inside the edge of the LOE and any holes in it
+        #[arg(long, default_value_t = 0.0)]
+        boundary_buffer: f64,
+        #[command(flatten)]
+        clipping: ClippingArgs,
+        #[command(flatten)]
+        solver: SolverArgs,
+        /// Strategies to compare, all of them by default
+        #[arg(long = "strategy", value_enum)]
+        strategies: Vec<StrategyName>,
+        /// Values tried per parameter by the grid search
+        #[arg(long, default_value_t = 3)]
+        steps: usize,
+        #[arg(long, default_value_t = 8)]
+        restarts: usize,
+        /// Layouts tried by the random restart and simulated annealing searches
+        #[arg(long, default_value_t = 400)]
+        iterations: usize,
+        #[arg(long, default_value_t = 0.05)]
+        initial_temperature: f64,
+        #[arg(long, default_value_t = 0.99)]
+        cooling_rate: f64,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Score against this many square features of --prior-size scattered over the LOE
+        /// instead of the recorded features
+        #[arg(long)]
+        prior_count: Option<usize>,
+        #[arg(long, default_value_t = 2.0)]
+        prior_size: f64,
+        #[command(flatten)]
+        detection: DetectionArgs,
+        /// Write the best layout found to GeoJSON, with the site's name added to the file name
+        /// when optimising several sites
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct SiteArgs {
+    #[arg(long, default_value = DATA_ROOT)]
+    pub data_root: PathBuf,
+    /// Sites to test, every site in the catalogue by default
+    #[arg(long = "site")]
+    pub site_names: Vec<String>,
+    /// Only test features on this layer
+    #[arg(long)]
+    pub layer: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct TrenchArgs {
+    /// continuous, parallel_array, standard_grid, test_pits, herringbone, spokes or rings
+    #[arg(long = "type")]
+    pub trench_type: TrenchType,
+    #[arg(long, default_value_t = 2.0)]
+    pub width: f64,
+    #[arg(long, default_value_t = 30.0)]
+    pub length: f64,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct DistributionArgs {
+    /// Spacing in meters
+    #[arg(long)]
+    pub spacing: Option<f64>,
+    /// Percentage coverage
+    #[arg(long)]
+    pub coverage: Option<f64>,
+    /// Budget, dug as the coverage it pays for at the LOE
+    #[arg(long)]
+    pub budget: Option<f64>,
+    /// Number of trenches, placed at random or as the coverage of that many on a lattice
+    #[arg(long)]
+    pub trenches: Option<usize>,
+}
+
+impl DistributionArgs {
+    pub fn distribution(&self) -> Distribution {
+        match (self.spacing, self.coverage, self.budget, self.trenches) {
+            (Some(spacing), _, _, _) => Distribution::Spacing(spacing),
+            (None, Some(coverage), _, _) => {
+                Distribution::Coverage(Percentage::new_from_percentage(coverage))
+            }
+            (None, None, Some(budget), _) => Distribution::Cost(budget),
+            (None, None, None, Some(trenches)) => Distribution::Count(trenches),
+            (None, None, None, None) => {
+                unreachable!("clap requires a spacing, coverage, budget or number of trenches")
+            }
+        }
+    }
+}
+
+// array trenches go on a square lattice unless told otherwise
+#[derive(Debug, Args)]
+pub struct LatticeArgs {
+    /// Where array trenches go: square, staggered with every other row shifted along by
+    /// --row-offset, hexagonal, or rectangular with rows --row-spacing apart
+    #[arg(long, value_enum, default_value_t = LatticeName::Square)]
+    pub lattice: LatticeName,
+    /// Fraction of the spacing every other row of a staggered lattice is shifted along
+    #[arg(long, default_value_t = 0.5)]
+    pub row_offset: f64,
+    /// Multiple of the spacing between the rows of a rectangular lattice
+    #[arg(long, default_value_t = 1.0)]
+    pub row_spacing: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LatticeName {
+    Square,
+    Staggered,
+    Hexagonal,
+    Rectangular,
+}
+
+impl LatticeArgs {
+    pub fn lattice(&self) -> Lattice {
+        match self.lattice {
+            LatticeName::Square => Lattice::Square,
+            LatticeName::Staggered => Lattice::Staggered {
+                row_offset: self.row_offset,
+            },
+            LatticeName::Hexagonal => Lattice::Hexagonal,
+            LatticeName::Rectangular => Lattice::Rectangular {
+                row_spacing: self.row_spacing,
+            },
+        }
+    }
+}
+
+// trenches are cut at the edge of where they may go unless told otherwise
+#[derive(Debug, Args)]
+pub struct ClippingArgs {
+    /// clip cuts trenches at the edge, whole-only drops any that cross it and shorten cuts
+    /// them back along their length to where their full width fits
+    #[arg(long, value_enum, default_value_t = ClippingName::Clip)]
+    pub clipping: ClippingName,
+    /// Meters a clipped trench must be longer than to be kept
+    #[arg(long, conflicts_with = "clipping")]
+    pub min_trench_length: Option<f64>,
+}
+
+impl ClippingArgs {
+    pub fn policy(&self) -> ClippingPolicy {
+        match (self.min_trench_length, self.clipping) {
+            (Some(minimum_length), _) => ClippingPolicy::MinimumLength(minimum_length),
+            (None, ClippingName::Clip) => ClippingPolicy::Clip,
+            (None, ClippingName::WholeOnly) => ClippingPolicy::WholeOnly,
+            (None, ClippingName::Shorten) => ClippingPolicy::Shorten,
+        }
+    }
+}
+
+// how closely a percentage coverage must be hit
+#[derive(Debug, Args)]
+pub struct SolverArgs {
+    /// Percentage points the achieved coverage may be either side of the target
+    #[arg(long, default_value_t = 0.05)]
+    pub coverage_tolerance: f64,
+    /// Shorten array trenches when no spacing hits the coverage within the tolerance
+    #[arg(long)]
+    pub adjust_length: bool,
+    /// Print every rotation, offset or seed that could not hit the coverage, rather than only
+    /// how many could not and why
+    #[arg(long)]
+    pub list_failures: bool,
+}
+
+impl SolverArgs {
+    pub fn solver(&self) -> CoverageSolver {
+        CoverageSolver {
+            tolerance: self.coverage_tolerance,
+            adjust_length: self.adjust_length,
+            list_failures: self.list_failures,
+            ..CoverageSolver::default()
+        }
+    }
+}
+
+// trenches go on a lattice unless placed at random
+#[derive(Debug, Args)]
+pub struct PlacementArgs {
+    /// lattice sweeps the pattern through its rotations and offsets, centre-line lays it along
+    /// the alignment of a corridor, random scatters trenches over the LOE and stratified-random
+    /// puts one in each cell of the lattice
+    #[arg(long, value_enum, default_value_t = PlacementName::Lattice)]
+    pub placement: PlacementName,
+    /// Seed of the first random layout
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Random layouts tested at each LOE, each with the next seed
+    #[arg(long, default_value_t = 100)]
+    pub layouts: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PlacementName {
+    Lattice,
+    CentreLine,
+    Random,
+    StratifiedRandom,
+}
+
+impl PlacementArgs {
+    pub fn placement(&self) -> Placement {
+        let (seed, layouts) = (self.seed, self.layouts);
+        match self.placement {
+            PlacementName::Lattice => Placement::Lattice,
+            PlacementName::CentreLine => Placement::CentreLine,
+            PlacementName::Random => Placement::Random { seed, layouts },
+            PlacementName::StratifiedRandom => Placement::StratifiedRandom { seed, layouts },
+        }
+    }
+}
+
+// layouts on a lattice are swept through every rotation unless turned to the LOE's principal axis
+#[derive(Debug, Args)]
+pub struct OrientationArgs {
+    /// swept tests every rotation, aligned runs trenches along the long axis of the LOE,
+    /// perpendicular across it and angle at --angle-to-axis, while auto sweeps and reports the
+    /// aligned layouts against the rest
+    #[arg(long, value_enum, default_value_t = OrientationName::Swept)]
+    pub orientation: OrientationName,
+    /// Degrees anticlockwise from the principal axis, for the angle orientation
+    #[arg(long, default_value_t = 0.0)]
+    pub angle_to_axis: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OrientationName {
+    Swept,
+    Aligned,
+    Perpendicular,
+    Angle,
+    Auto,
+}
+
+impl OrientationArgs {
+    pub fn orientation(&self) -> Orientation {
+        match self.orientation {
+            OrientationName::Swept => Orientation::Swept,
+            OrientationName::Aligned => Orientation::Aligned,
+            OrientationName::Perpendicular => Orientation::Perpendicular,
+            OrientationName::Angle => Orientation::Angle(self.angle_to_axis),
+            OrientationName::Auto => Orientation::Auto,
+        }
+    }
+}
+
+// rates a layout is costed at, and a budget converted to coverage with
+#[derive(Debug, Args)]
+pub struct CostArgs {
+    /// Meters trenches are dug down to
+    #[arg(long, default_value_t = CostModel::default().depth)]
+    pub depth: f64,
+    /// Cubic meters a machine digs in a day
+    #[arg(long, default_value_t = CostModel::default().excavation_rate)]
+    pub excavation_rate: f64,
+    /// Cubic meters a machine backfills in a day
+    #[arg(long, default_value_t = CostModel::default().backfill_rate)]
+    pub backfill_rate: f64,
+    /// Machine days to move from the end of one trench to the start of the next
+    #[arg(long, default_value_t = CostModel::default().move_time)]
+    pub move_time: f64,
+    /// Cost of a machine day, with its driver and supervision
+    #[arg(long, default_value_t = CostModel::default().day_rate)]
+    pub day_rate: f64,
+}
+
+impl CostArgs {
+    pub fn cost_model(&self) -> CostModel {
+        CostModel {
+            depth: self.depth,
+            excavation_rate: self.excavation_rate,
+            backfill_rate: self.backfill_rate,
+            move_time: self.move_time,
+            day_rate: self.day_rate,
+        }
+    }
+}
+
+// features count as found when touched by a trench unless a threshold is given
+#[derive(Debug, Args)]
+pub struct DetectionArgs {
+    /// Square meters of a feature that must be exposed for it to be found
+    #[arg(long)]
+    pub min_exposed_area: Option<f64>,
+    /// Fraction of a feature, between 0 and 1, that must be exposed for it to be found
+    #[arg(long)]
+    pub min_exposed_fraction: Option<f64>,
+}
+
+impl DetectionArgs {
+    pub fn rule(&self) -> DetectionRule {
+        match (self.min_exposed_area, self.min_exposed_fraction) {
+            (None, None) => DetectionRule::Intersects,
+            (minimum_area, minimum_fraction) => DetectionRule::Exposed {
+                minimum_area: minimum_area.unwrap_or(0.0),
+                minimum_fraction: minimum_fraction.unwrap_or(0.0),
+            },
+        }
+    }
+}
+
+// a layout discovers the site at a location when it finds enough features
+#[derive(Debug, Args)]
+pub struct DiscoveryArgs {
+    /// Features that must be found to discover the site
+    #[arg(long, default_value_t = 1)]
+    pub discovery_min_features: usize,
+    /// Distinct layers the features found must come from to discover the site
+    #[arg(long, default_value_t = 1)]
+    pub discovery_min_layers: usize,
+}
+
+impl DiscoveryArgs {
+    pub fn rule(&self) -> DiscoveryRule {
+        DiscoveryRule {
+            minimum_features: self.discovery_min_features,
+            minimum_layers: self.discovery_min_layers,
+        }
+    }
+}
+
+// exclusion zones are read from exclusions.geojson next to each loe.geojson
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CoverageBasisName {
+    Available,
+    LimitOfExcavation,
+}
+
+impl CoverageBasisName {
+    pub fn basis(self) -> CoverageBasis {
+        match self {
+            CoverageBasisName::Available => CoverageBasis::Available,
+            CoverageBasisName::LimitOfExcavation => CoverageBasis::LimitOfExcavation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ClippingName {
+    Clip,
+    WholeOnly,
+    Shorten,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StrategyName {
+    GridSearch,
+    RandomRestart,
+    SimulatedAnnealing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SettingOutFormatName {
+    Trenches,
+    SurveyPoints,
+}
@@ -0,0 +1,136 @@
+use geo::Area;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::{Excavation, Percentage, Structure, TrenchLayout};
+
+// what it takes to dig a layout, the machine moving once to the start of each trench
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CostModel {
+    pub depth: f64,           // meters, to the top of the archaeology
+    pub excavation_rate: f64, // cubic meters a machine digs in a day
+    pub backfill_rate: f64,   // cubic meters a machine backfills in a day
+    pub move_time: f64,       // machine days between the end of one trench and the next
+    pub day_rate: f64,        // cost of a machine day, with its driver and supervision
+}
+
+// spoil, time and money for a layout
+#[derive(Debug, Clone, Copy)]
+pub struct CostEstimate {
+    pub volume: f64, // cubic meters
+    pub machine_days: f64,
+    pub cost: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            depth: 0.5,
+            excavation_rate: 100.0,
+            backfill_rate: 200.0,
+            move_time: 0.02,
+            day_rate: 1000.0,
+        }
+    }
+}
+
+impl CostModel {
+    // moves may be a mean over layouts, so need not be whole
+    pub fn estimate(&self, trench_area: f64, moves: f64) -> CostEstimate {
+        let volume = trench_area * self.depth;
+        let machine_days =
+            volume / self.excavation_rate + volume / self.backfill_rate + moves * self.move_time;
+        CostEstimate {
+            volume,
+            machine_days,
+            cost: machine_days * self.day_rate,
+        }
+    }
+
+    pub fn estimate_layout(&self, layout: &TrenchLayout) -> CostEstimate {
+        self.estimate(
+            layout.trenches.unsigned_area(),
+            layout.trenches.0.len() as f64,
+        )
+    }
+
+    // coverage a budget pays for, before clipping makes trenches shorter and moves more frequent
+    pub fn coverage_of_budget(
+        &self,
+        budget: f64,
+        structure: Structure,
+        excavation: &Excavation,
+    ) -> Percentage {
+        let trench_area = structure.get_whole_trench_area(excavation);
+        let days_per_square_meter = self.depth / self.excavation_rate
+            + self.depth / self.backfill_rate
+            + self.move_time / trench_area;
+        let affordable_area = budget / self.day_rate / days_per_square_meter;
+        Percentage::new_from_percentage(
+            (affordable_area / excavation.coverage_area * 100.0).min(100.0),
+        )
+    }
+}
+
+impl fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}m³ of spoil, {:.2} machine days costing {:.0}",
+            self.volume, self.machine_days, self.cost
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{array, CoverageBasis, Distribution, TrenchConfig};
+    use geo::{coord, MultiPolygon, Rect};
+
+    fn excavation(size: f64) -> Excavation {
+        let square = Rect::new(coord! { x: 0.0, y: 0.0 }, coord! { x: size, y: size });
+        Excavation::new(
+            &MultiPolygon(vec![square.to_polygon()]),
+            &MultiPolygon(vec![]),
+            0.0,
+            CoverageBasis::Available,
+        )
+    }
+
+    #[test]
+    fn budget_buys_the_coverage_it_costs_to_dig() {
+        let model = CostModel::default();
+        let structure = TrenchConfig::standard_grid(
+            2.0,
+            30.0,
+            array::Lattice::Square,
+            Distribution::Spacing(60.0),
+        )
+        .unwrap()
+        .structure;
+        let excavation = excavation(100.0);
+        let coverage = model.coverage_of_budget(1000.0, structure, &excavation);
+        // digging that coverage in whole 60m² trenches spends the budget
+        let trench_area = coverage.percentage_as_decimal() * excavation.coverage_area;
+        let estimate = model.estimate(trench_area, trench_area / 60.0);
+        assert!((estimate.cost - 1000.0).abs() < 1e-6);
+        // twice the money, twice the coverage
+        let doubled = model.coverage_of_budget(2000.0, structure, &excavation);
+        assert!((doubled.0 - 2.0 * coverage.0).abs() < 1e-9);
+        // more than the whole excavation costs
+        assert_eq!(
+            model.coverage_of_budget(1e9, structure, &excavation).0,
+            100.0
+        );
+    }
+
+    #[test]
+    fn estimate_adds_digging_backfilling_and_moving() {
+        let estimate = CostModel::default().estimate(200.0, 5.0);
+        assert_eq!(estimate.volume, 100.0);
+        assert!((estimate.machine_days - (1.0 + 0.5 + 0.1)).abs() < 1e-12);
+        assert!((estimate.cost - 1600.0).abs() < 1e-9);
+    }
+}
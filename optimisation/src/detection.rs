@@ -0,0 +1,451 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::{ArchaeologicalFeature, LayoutMetadata};
+
+// when a feature touched by a trench counts as found
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionRule {
+    #[default]
+    Intersects, // any contact, even along an edge
+    Exposed {
+        #[serde(default)]
+        minimum_area: f64, // square meters
+        #[serde(default)]
+        minimum_fraction: f64, // of the feature's area, between 0 and 1
+    },
+}
+
+// when a layout counts as having discovered the site at a location, both minimums must be met
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct DiscoveryRule {
+    #[serde(default = "default_minimum")]
+    pub minimum_features: usize,
+    #[serde(default = "default_minimum")]
+    pub minimum_layers: usize, // distinct layers among the features found
+}
+
+// whether a single feature was found by a layout
+#[derive(Debug, Clone, Copy)]
+pub struct FeatureDetection {
+    pub feature_id: usize,
+    pub hit: bool,
+    pub intersected_area: f64, // square meters of the feature inside trenches
+}
+
+// the features of a test location and what each layout tested on it found
+#[derive(Debug, Clone)]
+pub struct LocationDetections {
+    pub site_name: String,
+    pub loe_i: String,
+    pub period: Option<String>,
+    pub geology: Option<String>,
+    pub features: Vec<ArchaeologicalFeature>,
+    pub layouts: Vec<Vec<FeatureDetection>>,
+    pub layout_metadata: Vec<Option<LayoutMetadata>>, // one for each of the layouts
+    pub aligned_rotation: Option<f64>, // along the principal axis of the LOE, for auto orientation
+}
+
+// a row of the detections CSV, one per feature per layout
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectionRecord {
+    pub run: String,
+    pub site: String,
+    pub loe: String,
+    pub period: Option<String>,
+    pub geology: Option<String>,
+    pub layout: usize,
+    pub feature_id: usize,
+    pub layer: Option<String>,
+    pub feature_type: Option<String>,
+    pub feature_area: f64,
+    pub hit: bool,
+    pub intersected_area: f64,
+    pub layout_coverage: Option<f64>,
+    pub layout_rotation: Option<f64>,
+    pub layout_spacing: Option<f64>,
+    pub layout_x_offset: Option<f64>,
+    pub layout_y_offset: Option<f64>,
+    pub layout_trenches: Option<usize>,
+    pub layout_clipped_trenches: Option<usize>,
+}
+
+// how often each layer and type of feature was found
+#[derive(Debug, Clone)]
+pub struct FeatureTypeSummary {
+    pub layer: Option<String>,
+    pub feature_type: Option<String>,
+    pub tests: usize, // features times layouts
+    pub found: usize,
+    pub mean_feature_area: f64,
+}
+
+impl DetectionRule {
+    // both thresholds must be exceeded, so a feature only grazed by a trench is missed
+    pub fn is_found(
+        &self,
+        feature: &ArchaeologicalFeature,
+        intersects: bool,
+        intersected_area: f64,
+    ) -> bool {
+        match *self {
+            DetectionRule::Intersects => intersects,
+            DetectionRule::Exposed {
+                minimum_area,
+                minimum_fraction,
+            } => {
+                // lines and points without a size have no area to expose
+                if feature.area == 0.0 {
+                    return intersects;
+                }
+                intersected_area > minimum_area
+                    && intersected_area / feature.area > minimum_fraction
+            }
+        }
+    }
+}
+
+fn default_minimum() -> usize {
+    1
+}
+
+impl Default for DiscoveryRule {
+    fn default() -> Self {
+        DiscoveryRule {
+            minimum_features: 1,
+            minimum_layers: 1,
+        }
+    }
+}
+
+impl DiscoveryRule {
+    pub fn is_discovered(
+        &self,
+        features: &[ArchaeologicalFeature],
+        detections: &[FeatureDetection],
+    ) -> bool {
+        let found: Vec<&ArchaeologicalFeature> = features
+            .iter()
+            .zip(detections.iter())
+            .filter(|(_, detection)| detection.hit)
+            .map(|(feature, _)| feature)
+            .collect();
+        // features without a layer count together as one
+        let layers: HashSet<Option<&str>> = found
+            .iter()
+            .map(|feature| feature.layer.as_deref())
+            .collect();
+        found.len() >= self.minimum_features && layers.len() >= self.minimum_layers
+    }
+}
+
+impl fmt::Display for DiscoveryRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} features from {} layers",
+            self.minimum_features, self.minimum_layers
+        )
+    }
+}
+
+impl fmt::Display for DetectionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectionRule::Intersects => write!(f, "intersects"),
+            DetectionRule::Exposed {
+                minimum_area,
+                minimum_fraction,
+            } => write!(
+                f,
+                "exposed over {}m² and {}%",
+                minimum_area,
+                minimum_fraction * 100.0
+            ),
+        }
+    }
+}
+
+pub fn count_found_and_missed(detections: &[FeatureDetection]) -> (i32, i32) {
+    let found = detections.iter().filter(|detection| detection.hit).count() as i32;
+    (found, detections.len() as i32 - found)
+}
+
+impl LocationDetections {
+    pub fn count_found_and_missed(&self) -> (i32, i32) {
+        self.layouts
+            .iter()
+            .map(|detections| count_found_and_missed(detections))
+            .fold((0, 0), |(total_found, total_missed), (found, missed)| {
+                (total_found + found, total_missed + missed)
+            })
+    }
+
+    // percentage of features found by each layout
+    pub fn detection_rates(&self) -> Vec<f64> {
+        self.layouts
+            .iter()
+            .map(|detections| {
+                let (found, missed) = count_found_and_missed(detections);
+                found as f64 / (found + missed) as f64 * 100.0
+            })
+            .collect()
+    }
+
+    // percentage found by each layout turned along the principal axis of the LOE
+    pub fn aligned_detection_rates(&self) -> Vec<f64> {
+        let Some(aligned_rotation) = self.aligned_rotation else {
+            return Vec::new();
+        };
+        self.detection_rates()
+            .into_iter()
+            .zip(self.layout_metadata.iter())
+            .filter(|(_, metadata)| {
+                metadata.is_some_and(|metadata| metadata.rotation == aligned_rotation)
+            })
+            .map(|(rate, _)| rate)
+            .collect()
+    }
+
+    // layouts that discovered the site at this location
+    pub fn count_discovered(&self, rule: &DiscoveryRule) -> i32 {
+        self.layouts
+            .iter()
+            .filter(|detections| rule.is_discovered(&self.features, detections))
+            .count() as i32
+    }
+
+    // what each layout was, for those generated rather than read without it
+    pub fn metadata(&self) -> Vec<LayoutMetadata> {
+        self.layout_metadata.iter().flatten().copied().collect()
+    }
+
+    pub fn records(&self, run: &str) -> Vec<DetectionRecord> {
+        self.layouts
+            .iter()
+            .zip(self.layout_metadata.iter())
+            .enumerate()
+            .flat_map(|(layout_i, (detections, metadata))| {
+                self.features
+                    .iter()
+                    .zip(detections.iter())
+                    .map(move |(feature, detection)| DetectionRecord {
+                        run: run.to_string(),
+                        site: self.site_name.clone(),
+                        loe: self.loe_i.clone(),
+                        period: self.period.clone(),
+                        geology: self.geology.clone(),
+                        layout: layout_i + 1,
+                        feature_id: feature.id,
+                        layer: feature.layer.clone(),
+                        feature_type: feature.feature_type.clone(),
+                        feature_area: feature.area,
+                        hit: detection.hit,
+                        intersected_area: detection.intersected_area,
+                        layout_coverage: metadata.map(|metadata| metadata.coverage),
+                        layout_rotation: metadata.map(|metadata| metadata.rotation),
+                        layout_spacing: metadata.map(|metadata| metadata.spacing),
+                        layout_x_offset: metadata.map(|metadata| metadata.x_offset),
+                        layout_y_offset: metadata.map(|metadata| metadata.y_offset),
+                        layout_trenches: metadata.map(|metadata| metadata.trenches),
+                        layout_clipped_trenches: metadata.map(|metadata| metadata.clipped_trenches),
+                    })
+            })
+            .collect()
+    }
+}
+
+impl FeatureTypeSummary {
+    pub fn percentage_found(&self) -> f64 {
+        self.found as f64 / self.tests as f64 * 100.0
+    }
+}
+
+pub fn summarise_by_feature_type(records: &[DetectionRecord]) -> Vec<FeatureTypeSummary> {
+    let mut summaries: BTreeMap<(Option<String>, Option<String>), FeatureTypeSummary> =
+        BTreeMap::new();
+    for record in records {
+        let summary = summaries
+            .entry((record.layer.clone(), record.feature_type.clone()))
+            .or_insert_with(|| FeatureTypeSummary {
+                layer: record.layer.clone(),
+                feature_type: record.feature_type.clone(),
+                tests: 0,
+                found: 0,
+                mean_feature_area: 0.0,
+            });
+        summary.tests += 1;
+        if record.hit {
+            summary.found += 1;
+        }
+        // running mean, so large datasets don't need a second pass
+        summary.mean_feature_area +=
+            (record.feature_area - summary.mean_feature_area) / summary.tests as f64;
+    }
+    summaries.into_values().collect()
+}
+
+pub fn write_detections(records: &[DetectionRecord], detections_path: &Path) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(fs_err::File::create(detections_path)?);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FeatureGeometry;
+    use geo::{coord, MultiPoint, MultiPolygon, Point, Rect};
+
+    // a square feature of the given side
+    fn feature(
+        id: usize,
+        layer: Option<&str>,
+        feature_type: Option<&str>,
+        side: f64,
+    ) -> ArchaeologicalFeature {
+        let square = Rect::new(coord! { x: 0.0, y: 0.0 }, coord! { x: side, y: side }).to_polygon();
+        ArchaeologicalFeature::new(
+            id,
+            FeatureGeometry::Polygon(MultiPolygon(vec![square])),
+            layer.map(str::to_string),
+            feature_type.map(str::to_string),
+        )
+    }
+
+    fn detection(feature_id: usize, hit: bool, intersected_area: f64) -> FeatureDetection {
+        FeatureDetection {
+            feature_id,
+            hit,
+            intersected_area,
+        }
+    }
+
+    fn location(
+        features: Vec<ArchaeologicalFeature>,
+        layouts: Vec<Vec<FeatureDetection>>,
+    ) -> LocationDetections {
+        LocationDetections {
+            site_name: "site".to_string(),
+            loe_i: "0".to_string(),
+            period: Some("Roman".to_string()),
+            geology: None,
+            features,
+            layout_metadata: vec![None; layouts.len()],
+            layouts,
+            aligned_rotation: None,
+        }
+    }
+
+    #[test]
+    fn records_are_one_per_feature_per_layout() {
+        let detections = location(
+            vec![
+                feature(1, Some("Roman"), Some("pit"), 2.0),
+                feature(2, None, Some("ditch"), 4.0),
+            ],
+            vec![
+                vec![detection(1, true, 1.5), detection(2, false, 0.0)],
+                vec![detection(1, false, 0.0), detection(2, true, 3.0)],
+            ],
+        );
+        let records = detections.records("run");
+        assert_eq!(records.len(), 4);
+        let last = &records[3];
+        assert_eq!((last.layout, last.feature_id), (2, 2));
+        assert_eq!(last.feature_type.as_deref(), Some("ditch"));
+        assert_eq!(last.feature_area, 16.0);
+        assert!(last.hit);
+        assert_eq!(last.intersected_area, 3.0);
+        assert_eq!(last.period.as_deref(), Some("Roman"));
+        assert_eq!(last.layout_coverage, None);
+        assert_eq!(detections.count_found_and_missed(), (2, 2));
+
+        let summaries = summarise_by_feature_type(&records);
+        assert_eq!(summaries.len(), 2);
+        let pits = summaries
+            .iter()
+            .find(|summary| summary.feature_type.as_deref() == Some("pit"))
+            .unwrap();
+        assert_eq!((pits.tests, pits.found), (2, 1));
+        assert_eq!(pits.percentage_found(), 50.0);
+        assert_eq!(pits.mean_feature_area, 4.0);
+    }
+
+    #[test]
+    fn exposed_features_must_clear_both_thresholds() {
+        let pit = feature(1, None, None, 10.0);
+        let rule = DetectionRule::Exposed {
+            minimum_area: 5.0,
+            minimum_fraction: 0.1,
+        };
+        assert!(rule.is_found(&pit, true, 12.0));
+        // under the area, then under the fraction of 100m²
+        assert!(!rule.is_found(&pit, true, 4.0));
+        let fraction = DetectionRule::Exposed {
+            minimum_area: 0.0,
+            minimum_fraction: 0.1,
+        };
+        assert!(!fraction.is_found(&pit, true, 8.0));
+        // a graze along an edge exposes nothing
+        assert!(DetectionRule::Intersects.is_found(&pit, true, 0.0));
+        assert!(!fraction.is_found(&pit, true, 0.0));
+        // without a size, touching is enough
+        let posthole = ArchaeologicalFeature::new(
+            2,
+            FeatureGeometry::Point {
+                points: MultiPoint(vec![Point::new(0.0, 0.0)]),
+                radius: None,
+            },
+            None,
+            None,
+        );
+        assert!(rule.is_found(&posthole, true, 0.0));
+        assert!(!rule.is_found(&posthole, false, 0.0));
+    }
+
+    #[test]
+    fn discovery_needs_enough_features_from_enough_layers() {
+        let features = vec![
+            feature(1, Some("Roman"), None, 1.0),
+            feature(2, Some("Roman"), None, 1.0),
+            feature(3, Some("Iron Age"), None, 1.0),
+            feature(4, None, None, 1.0),
+        ];
+        let found = |hits: [bool; 4]| -> Vec<FeatureDetection> {
+            hits.iter()
+                .enumerate()
+                .map(|(i, hit)| detection(i + 1, *hit, 0.0))
+                .collect()
+        };
+        let rule = DiscoveryRule {
+            minimum_features: 2,
+            minimum_layers: 2,
+        };
+        assert!(!rule.is_discovered(&features, &found([true, true, false, false])));
+        assert!(!rule.is_discovered(&features, &found([false, false, true, false])));
+        assert!(rule.is_discovered(&features, &found([true, false, true, false])));
+        // features without a layer are a layer of their own
+        assert!(rule.is_discovered(&features, &found([true, false, false, true])));
+        assert!(
+            DiscoveryRule::default().is_discovered(&features, &found([false, false, false, true]))
+        );
+        assert!(!DiscoveryRule::default().is_discovered(&features, &found([false; 4])));
+
+        let detections = location(
+            features.clone(),
+            vec![
+                found([true, true, false, false]),
+                found([true, false, true, false]),
+            ],
+        );
+        assert_eq!(detections.count_discovered(&rule), 1);
+    }
+}
@@ -0,0 +1,382 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::array::Lattice;
+use crate::catalogue::SiteCatalogue;
+use crate::cost::CostModel;
+use crate::detection::{DetectionRule, DiscoveryRule};
+use crate::statistics::{LayoutSummary, Summary};
+use crate::{
+    ClippingPolicy, CoverageBasis, CoverageSolver, Distribution, Orientation, Percentage,
+    Placement, TrenchConfig, TrenchType,
+};
+
+// an experiment file lists trench configs, distributions, layers and site groups,
+// every combination of which is run and given a row in the results
+#[derive(Debug, Clone, Deserialize)]
+pub struct Experiment {
+    pub name: String,
+    pub data_root: Option<PathBuf>,  // relative to the experiment file
+    pub output: Option<PathBuf>,     // relative to the experiment file, defaults to <file>.csv
+    pub detections: Option<PathBuf>, // relative to the experiment file, per-feature results when set
+    pub statistics: Option<PathBuf>, // relative to the experiment file, per-LOE detection rates when set
+    #[serde(default = "default_offset_steps")]
+    pub offset_steps: i32,
+    #[serde(default)]
+    pub coverage_of: CoverageBasis, // the LOE less any exclusion zones and boundary buffer by default
+    #[serde(default)]
+    pub boundary_buffer: f64, // meters
+    #[serde(default)]
+    pub clipping: ClippingPolicy, // trenches cut at the edge by default
+    #[serde(default)]
+    pub solver: CoverageSolver,
+    #[serde(default)]
+    pub cost: CostModel,
+    #[serde(default)]
+    pub placement: Placement, // trenches on a lattice by default
+    #[serde(default)]
+    pub orientation: Orientation, // every rotation swept by default
+    #[serde(default)]
+    pub detection: DetectionRule, // any contact with a trench by default
+    #[serde(default)]
+    pub discovery: DiscoveryRule, // a single feature by default
+    pub trenches: Vec<TrenchSpec>,
+    pub distributions: Vec<DistributionSpec>,
+    #[serde(default)]
+    pub layers: Vec<String>, // every layer when empty
+    #[serde(default)]
+    pub site_groups: Vec<SiteGroup>, // every site in the catalogue when empty
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrenchSpec {
+    #[serde(rename = "type")]
+    pub trench_type: TrenchType,
+    pub width: f64,
+    #[serde(default = "default_length")]
+    pub length: f64, // unused by continuous trenches and test pits
+    #[serde(default)]
+    pub lattice: Lattice, // square by default, unused by continuous trenches
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionSpec {
+    Spacing(f64),    // meters
+    Coverage(f64),   // percentage coverage
+    Budget(f64),     // dug as the coverage it pays for at each LOE
+    Trenches(usize), // the same number of trenches at each LOE
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SiteGroup {
+    pub name: String,
+    pub sites: Vec<String>,
+}
+
+// a single combination of the experiment
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub trench: TrenchSpec,
+    pub distribution: DistributionSpec,
+    pub config: TrenchConfig,
+    pub layer: Option<String>,
+    pub site_group: SiteGroup,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    pub experiment: String,
+    pub run: usize, // matches the run of the detections
+    pub site_group: String,
+    pub layer: String,
+    pub trench_type: String,
+    pub width: f64,
+    pub length: f64,
+    pub lattice: String,
+    pub spacing: Option<f64>,
+    pub coverage: Option<f64>,
+    pub budget: Option<f64>,
+    pub trenches: Option<usize>,
+    pub placement: String,
+    pub orientation: String,
+    pub coverage_of: String,
+    pub boundary_buffer: f64,
+    pub clipping: String,
+    pub coverage_tolerance: f64,
+    pub adjust_length: bool,
+    pub offset_steps: i32,
+    pub detection: String,
+    pub locations: usize,
+    pub patterns_tested: i32,
+    pub features_found: i32,
+    pub features_missed: i32,
+    pub percentage_found: f64,
+    pub site_discovery: String,
+    pub layouts_discovering_site: i32,
+    pub site_discovery_probability: f64, // percentage of layouts
+    // spread of the percentage found by each layout
+    pub rate_mean: Option<f64>,
+    pub rate_median: Option<f64>,
+    pub rate_standard_deviation: Option<f64>,
+    pub rate_min: Option<f64>,
+    pub rate_max: Option<f64>,
+    pub rate_percentile_5: Option<f64>,
+    pub rate_percentile_95: Option<f64>,
+    pub rate_confidence_low: Option<f64>,
+    pub rate_confidence_high: Option<f64>,
+    pub aligned_rate_mean: Option<f64>, // of the layouts along the principal axis, for auto orientation
+    // what the layouts came to on average
+    pub mean_coverage: Option<f64>,
+    pub mean_trenches: Option<f64>,
+    pub mean_clipped_trenches: Option<f64>,
+    pub mean_trench_length: Option<f64>,
+    pub mean_machine_days: Option<f64>,
+    pub mean_cost: Option<f64>,
+}
+
+fn default_offset_steps() -> i32 {
+    1
+}
+
+fn default_length() -> f64 {
+    30.0
+}
+
+impl DistributionSpec {
+    pub fn distribution(&self) -> Distribution {
+        match *self {
+            DistributionSpec::Spacing(spacing) => Distribution::Spacing(spacing),
+            DistributionSpec::Coverage(coverage) => {
+                Distribution::Coverage(Percentage::new_from_percentage(coverage))
+            }
+            DistributionSpec::Budget(budget) => Distribution::Cost(budget),
+            DistributionSpec::Trenches(trenches) => Distribution::Count(trenches),
+        }
+    }
+}
+
+impl Experiment {
+    // TOML or JSON, chosen by the file extension
+    pub fn load(experiment_path: &Path) -> Result<Self> {
+        let contents = fs_err::read_to_string(experiment_path)?;
+        let mut experiment: Experiment =
+            match experiment_path.extension().and_then(|ext| ext.to_str()) {
+                Some("toml") => toml::from_str(&contents)
+                    .with_context(|| format!("Invalid experiment file {:?}", experiment_path))?,
+                Some("json") => serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid experiment file {:?}", experiment_path))?,
+                _ => {
+                    return Err(anyhow!(
+                        "Experiment file {:?} must be .toml or .json",
+                        experiment_path
+                    ))
+                }
+            };
+        let experiment_folder = experiment_path.parent().unwrap_or(Path::new(""));
+        experiment.data_root = experiment
+            .data_root
+            .map(|data_root| experiment_folder.join(data_root));
+        experiment.detections = experiment
+            .detections
+            .map(|detections| experiment_folder.join(detections));
+        experiment.statistics = experiment
+            .statistics
+            .map(|statistics| experiment_folder.join(statistics));
+        experiment.output = Some(match experiment.output {
+            Some(output) => experiment_folder.join(output),
+            None => experiment_path.with_extension("csv"),
+        });
+        Ok(experiment)
+    }
+
+    // every trench, distribution, layer and site group, checking the configs before anything runs
+    pub fn runs(&self, catalogue: &SiteCatalogue) -> Result<Vec<Run>> {
+        let layers: Vec<Option<String>> = if self.layers.is_empty() {
+            vec![None]
+        } else {
+            self.layers.iter().cloned().map(Some).collect()
+        };
+        let site_groups = if self.site_groups.is_empty() {
+            vec![SiteGroup {
+                name: "all".to_string(),
+                sites: catalogue
+                    .sites
+                    .iter()
+                    .map(|site| site.name.clone())
+                    .collect(),
+            }]
+        } else {
+            self.site_groups.clone()
+        };
+        for site_group in site_groups.iter() {
+            for site_name in site_group.sites.iter() {
+                if !catalogue.sites.iter().any(|site| &site.name == site_name) {
+                    return Err(anyhow!(
+                        "Site {} in group {} is not in the catalogue",
+                        site_name,
+                        site_group.name
+                    ));
+                }
+            }
+        }
+
+        let mut runs = Vec::new();
+        for trench in self.trenches.iter() {
+            for distribution in self.distributions.iter() {
+                let config = TrenchConfig::of_type(
+                    trench.trench_type,
+                    trench.width,
+                    trench.length,
+                    trench.lattice,
+                    distribution.distribution(),
+                )
+                .with_context(|| format!("Invalid {:?} with {:?}", trench, distribution))?
+                .with_offset_steps(self.offset_steps)
+                .with_coverage_basis(self.coverage_of)
+                .with_boundary_buffer(self.boundary_buffer)
+                .with_clipping(self.clipping)
+                .with_solver(self.solver)
+                .with_cost_model(self.cost)
+                .with_placement(self.placement)
+                .with_orientation(self.orientation);
+                for site_group in site_groups.iter() {
+                    for layer in layers.iter() {
+                        runs.push(Run {
+                            trench: trench.clone(),
+                            distribution: *distribution,
+                            config,
+                            layer: layer.clone(),
+                            site_group: site_group.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(runs)
+    }
+}
+
+impl RunResult {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        experiment: &Experiment,
+        run_i: usize,
+        run: &Run,
+        locations: usize,
+        patterns_tested: i32,
+        features_found: i32,
+        features_missed: i32,
+        layouts_discovering_site: i32,
+    ) -> Self {
+        let (spacing, coverage, budget, trenches) = match run.distribution {
+            DistributionSpec::Spacing(spacing) => (Some(spacing), None, None, None),
+            DistributionSpec::Coverage(coverage) => (None, Some(coverage), None, None),
+            DistributionSpec::Budget(budget) => (None, None, Some(budget), None),
+            DistributionSpec::Trenches(trenches) => (None, None, None, Some(trenches)),
+        };
+        RunResult {
+            experiment: experiment.name.clone(),
+            run: run_i,
+            site_group: run.site_group.name.clone(),
+            layer: run.layer.clone().unwrap_or_else(|| "all".to_string()),
+            trench_type: run.trench.trench_type.to_string(),
+            width: run.trench.width,
+            length: run.trench.length,
+            lattice: run.trench.lattice.to_string(),
+            spacing,
+            coverage,
+            budget,
+            trenches,
+            placement: experiment.placement.to_string(),
+            orientation: experiment.orientation.to_string(),
+            coverage_of: experiment.coverage_of.to_string(),
+            boundary_buffer: experiment.boundary_buffer,
+            clipping: experiment.clipping.to_string(),
+            coverage_tolerance: experiment.solver.tolerance,
+            adjust_length: experiment.solver.adjust_length,
+            offset_steps: experiment.offset_steps,
+            detection: experiment.detection.to_string(),
+            locations,
+            patterns_tested,
+            features_found,
+            features_missed,
+            percentage_found: features_found as f64 / (features_found + features_missed) as f64
+                * 100.0,
+            site_discovery: experiment.discovery.to_string(),
+            layouts_discovering_site,
+            site_discovery_probability: layouts_discovering_site as f64 / patterns_tested as f64
+                * 100.0,
+            rate_mean: None,
+            rate_median: None,
+            rate_standard_deviation: None,
+            rate_min: None,
+            rate_max: None,
+            rate_percentile_5: None,
+            rate_percentile_95: None,
+            rate_confidence_low: None,
+            rate_confidence_high: None,
+            aligned_rate_mean: None,
+            mean_coverage: None,
+            mean_trenches: None,
+            mean_clipped_trenches: None,
+            mean_trench_length: None,
+            mean_machine_days: None,
+            mean_cost: None,
+        }
+    }
+
+    pub fn with_detection_rates(self, summary: Option<Summary>) -> Self {
+        match summary {
+            Some(summary) => RunResult {
+                rate_mean: Some(summary.mean),
+                rate_median: Some(summary.median),
+                rate_standard_deviation: Some(summary.standard_deviation),
+                rate_min: Some(summary.min),
+                rate_max: Some(summary.max),
+                rate_percentile_5: Some(summary.percentile_5),
+                rate_percentile_95: Some(summary.percentile_95),
+                rate_confidence_low: Some(summary.confidence_interval.0),
+                rate_confidence_high: Some(summary.confidence_interval.1),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    pub fn with_aligned_detection_rates(self, summary: Option<Summary>) -> Self {
+        RunResult {
+            aligned_rate_mean: summary.map(|summary| summary.mean),
+            ..self
+        }
+    }
+
+    // the cost is of the mean layout
+    pub fn with_layout_summary(
+        self,
+        layout_summary: Option<LayoutSummary>,
+        cost: &CostModel,
+    ) -> Self {
+        let estimate = layout_summary
+            .map(|summary| cost.estimate(summary.mean_trench_area, summary.mean_trenches));
+        RunResult {
+            mean_coverage: layout_summary.map(|summary| summary.mean_coverage),
+            mean_trenches: layout_summary.map(|summary| summary.mean_trenches),
+            mean_clipped_trenches: layout_summary.map(|summary| summary.mean_clipped_trenches),
+            mean_trench_length: layout_summary.map(|summary| summary.mean_trench_length),
+            mean_machine_days: estimate.map(|estimate| estimate.machine_days),
+            mean_cost: estimate.map(|estimate| estimate.cost),
+            ..self
+        }
+    }
+}
+
+pub fn write_results(results: &[RunResult], results_path: &Path) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(fs_err::File::create(results_path)?);
+    for result in results {
+        writer.serialize(result)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -1,6 +1,189 @@
-use geo::{Intersects, Polygon};
-use trenching_optimisation::TrenchLayout;
+use geo::{Area, BooleanOps, EuclideanDistance, EuclideanLength, Intersects, MultiPolygon};
+use trenching_optimisation::buffer::circle;
+use trenching_optimisation::detection::{DetectionRule, FeatureDetection};
+use trenching_optimisation::{ArchaeologicalFeature, FeatureGeometry, TrenchLayout};
 
-pub fn test(feature: &Polygon<f64>, trenches: &TrenchLayout) -> bool {
-    feature.intersects(&trenches.0)
+// lines and points with a size are found by a trench within half the width or the radius
+pub fn test(feature: &FeatureGeometry, trenches: &TrenchLayout) -> bool {
+    match feature {
+        FeatureGeometry::Polygon(polygons) => polygons.intersects(&trenches.trenches),
+        FeatureGeometry::Line {
+            lines,
+            buffer_width,
+        } => {
+            let reach = buffer_width.unwrap_or(0.0) / 2.0;
+            trenches.trenches.iter().any(|trench| {
+                lines
+                    .iter()
+                    .any(|line| trench.euclidean_distance(line) <= reach)
+            })
+        }
+        FeatureGeometry::Point { points, radius } => {
+            let reach = radius.unwrap_or(0.0);
+            trenches.trenches.iter().any(|trench| {
+                points
+                    .iter()
+                    .any(|point| trench.euclidean_distance(point) <= reach)
+            })
+        }
+    }
+}
+
+fn intersected_area(polygons: &MultiPolygon<f64>, trenches: &TrenchLayout) -> f64 {
+    trenches
+        .trenches
+        .iter()
+        .filter(|trench| trench.intersects(polygons))
+        .flat_map(|trench| {
+            polygons
+                .iter()
+                .filter(|polygon| trench.intersects(*polygon))
+                .map(|polygon| {
+                    trench
+                        .boolean_op(polygon, geo::OpType::Intersection)
+                        .unsigned_area()
+                })
+        })
+        .fold(0.0, |total, area| total + area)
+}
+
+// trenches never overlap, so the exposed area is the sum over the trenches touching the feature
+pub fn detect(
+    feature: &ArchaeologicalFeature,
+    trenches: &TrenchLayout,
+    rule: &DetectionRule,
+) -> FeatureDetection {
+    let intersected_area = match &feature.geometry {
+        FeatureGeometry::Polygon(polygons) => intersected_area(polygons, trenches),
+        // the length of line inside the trenches, as wide as the feature
+        FeatureGeometry::Line {
+            lines,
+            buffer_width: Some(width),
+        } => trenches.trenches.clip(lines, false).euclidean_length() * width,
+        FeatureGeometry::Point {
+            points,
+            radius: Some(radius),
+        } => {
+            let circles = MultiPolygon(points.iter().map(|point| circle(point, *radius)).collect());
+            intersected_area(&circles, trenches)
+        }
+        _ => 0.0,
+    };
+    FeatureDetection {
+        feature_id: feature.id,
+        hit: rule.is_found(feature, test(&feature.geometry, trenches), intersected_area),
+        intersected_area,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{coord, Polygon, Rect};
+
+    fn rectangle(min: (f64, f64), max: (f64, f64)) -> Polygon {
+        Rect::new(coord! { x: min.0, y: min.1 }, coord! { x: max.0, y: max.1 }).to_polygon()
+    }
+
+    // two trenches 2m wide, 10m apart
+    fn trenches() -> TrenchLayout {
+        TrenchLayout {
+            trenches: MultiPolygon(vec![
+                rectangle((0.0, 0.0), (2.0, 30.0)),
+                rectangle((12.0, 0.0), (14.0, 30.0)),
+            ]),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn exposed_area_is_summed_over_the_trenches() {
+        let feature = ArchaeologicalFeature::new(
+            3,
+            FeatureGeometry::Polygon(MultiPolygon(vec![rectangle((1.0, 0.0), (13.0, 5.0))])),
+            None,
+            None,
+        );
+        let detection = detect(&feature, &trenches(), &DetectionRule::Intersects);
+        assert_eq!(detection.feature_id, 3);
+        assert!(detection.hit);
+        assert!((detection.intersected_area - 10.0).abs() < 1e-9);
+
+        let between = ArchaeologicalFeature::new(
+            4,
+            FeatureGeometry::Polygon(MultiPolygon(vec![rectangle((4.0, 0.0), (10.0, 5.0))])),
+            None,
+            None,
+        );
+        let detection = detect(&between, &trenches(), &DetectionRule::Intersects);
+        assert!(!detection.hit);
+        assert_eq!(detection.intersected_area, 0.0);
+    }
+
+    fn line(buffer_width: Option<f64>, from: (f64, f64), to: (f64, f64)) -> ArchaeologicalFeature {
+        let lines = geo::MultiLineString(vec![geo::LineString::from(vec![from, to])]);
+        ArchaeologicalFeature::new(
+            1,
+            FeatureGeometry::Line {
+                lines,
+                buffer_width,
+            },
+            None,
+            None,
+        )
+    }
+
+    fn point(radius: Option<f64>, x: f64, y: f64) -> ArchaeologicalFeature {
+        let points = geo::MultiPoint(vec![geo::Point::new(x, y)]);
+        ArchaeologicalFeature::new(1, FeatureGeometry::Point { points, radius }, None, None)
+    }
+
+    #[test]
+    fn lines_are_hit_within_half_their_width() {
+        let rule = DetectionRule::Intersects;
+        // 3m from the nearest trench
+        assert!(
+            detect(
+                &line(Some(6.0), (5.0, 0.0), (5.0, 30.0)),
+                &trenches(),
+                &rule
+            )
+            .hit
+        );
+        assert!(
+            !detect(
+                &line(Some(4.0), (5.0, 0.0), (5.0, 30.0)),
+                &trenches(),
+                &rule
+            )
+            .hit
+        );
+        assert!(!detect(&line(None, (5.0, 0.0), (5.0, 30.0)), &trenches(), &rule).hit);
+        // crossing both trenches for 4m
+        let crossing = detect(
+            &line(Some(1.0), (-5.0, 10.0), (20.0, 10.0)),
+            &trenches(),
+            &rule,
+        );
+        assert!(crossing.hit);
+        assert!((crossing.intersected_area - 4.0).abs() < 1e-9);
+        let no_width = detect(&line(None, (-5.0, 10.0), (20.0, 10.0)), &trenches(), &rule);
+        assert!(no_width.hit);
+        assert_eq!(no_width.intersected_area, 0.0);
+    }
+
+    #[test]
+    fn points_are_hit_within_their_radius() {
+        let rule = DetectionRule::Intersects;
+        // 5m from the nearest trench
+        assert!(detect(&point(Some(5.0), 7.0, 5.0), &trenches(), &rule).hit);
+        assert!(!detect(&point(Some(4.0), 7.0, 5.0), &trenches(), &rule).hit);
+        assert!(!detect(&point(None, 7.0, 5.0), &trenches(), &rule).hit);
+        assert!(detect(&point(None, 1.0, 5.0), &trenches(), &rule).hit);
+        // a posthole wholly inside a trench is exposed in full
+        let posthole = point(Some(0.5), 1.0, 5.0);
+        let detection = detect(&posthole, &trenches(), &rule);
+        assert!(detection.hit);
+        assert!((detection.intersected_area - posthole.area).abs() / posthole.area < 0.01);
+    }
 }
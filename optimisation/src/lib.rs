@@ -1,18 +1,213 @@
 use anyhow::{anyhow, Result};
 use fs_err::File;
-use geo::{coord, Coord, LineString, MultiPolygon, Polygon};
-use geojson::{Feature, GeoJson, Geometry, Value};
-use std::io::BufReader;
+use geo::{
+    coord, Area, BooleanOps, Coord, EuclideanLength, LineString, MinimumRotatedRect,
+    MultiLineString, MultiPoint, MultiPolygon, Point,
+};
+use geojson::feature::Id;
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, JsonValue, Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
 use std::time::Instant;
 
-#[derive(Debug)]
-pub struct TrenchLayout(pub MultiPolygon<f64>);
+pub mod buffer;
+pub mod catalogue;
+pub mod cost;
+pub mod detection;
+pub mod experiment;
+pub mod setting_out;
+pub mod statistics;
+
+use catalogue::SiteCatalogue;
+use cost::CostModel;
+
+#[derive(Debug, Clone)]
+pub struct TrenchLayout {
+    pub trenches: MultiPolygon<f64>,
+    pub metadata: Option<LayoutMetadata>, // None for a layout read from a file without it
+}
 // TODO: add impl for intersects to TrenchLayout
 
+// how a layout was placed and what it came to, saved with it and carried into every result
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LayoutMetadata {
+    pub rotation: f64, // degrees
+    pub spacing: f64,  // meters
+    pub x_offset: f64,
+    pub y_offset: f64,
+    pub trench_width: f64,
+    pub coverage: f64,     // percentage achieved
+    pub total_area: f64,   // square meters
+    pub total_length: f64, // meters, the area over the width
+    pub trenches: usize,
+    pub clipped_trenches: usize, // cut short by the edge of where trenches may go
+}
+
 #[derive(Debug)]
 pub struct TestLocation {
-    pub limit_of_excavation: Polygon,
-    pub features: Vec<Polygon<f64>>,
+    pub site_name: String,
+    pub loe_i: String,
+    pub period: Option<String>, // of the site, from the manifest
+    pub geology: Option<String>,
+    pub limit_of_excavation: MultiPolygon, // islands and holes, such as retained hedgerows, are kept
+    pub exclusions: MultiPolygon, // buried services, root protection areas and the like, may be empty
+    pub centre_line: Option<LineString>, // of a road or pipeline, found from the LOE when left out
+    pub focus: Option<Point>, // of spokes and rings, such as a known monument, the LOE centroid when left out
+    pub features: Vec<ArchaeologicalFeature>,
+}
+
+impl TestLocation {
+    pub fn excavation(&self, config: &TrenchConfig) -> Excavation {
+        Excavation::new(
+            &self.limit_of_excavation,
+            &self.exclusions,
+            config.boundary_buffer,
+            config.coverage_basis,
+        )
+        .with_centre_line(self.centre_line.clone())
+        .with_focus(self.focus)
+    }
+}
+
+// the LOE less its exclusion zones and boundary buffer, which is all trenches are cut to
+#[derive(Debug, Clone)]
+pub struct Excavation {
+    pub available: MultiPolygon,
+    pub coverage_area: f64, // square meters a layout's coverage is a percentage of
+    pub centre_line: Option<LineString>, // supplied alignment of a corridor
+    pub focus: Option<Point>, // supplied centre of spokes and rings
+    principal_axis: Option<Degree>, // of the LOE itself, so exclusion zones do not turn it
+}
+
+impl Excavation {
+    pub fn new(
+        limit_of_excavation: &MultiPolygon,
+        exclusions: &MultiPolygon,
+        boundary_buffer: f64,
+        coverage_basis: CoverageBasis,
+    ) -> Self {
+        let placement_area = buffer::inward_buffer(limit_of_excavation, boundary_buffer);
+        let available = if exclusions.0.is_empty() {
+            placement_area
+        } else {
+            placement_area.difference(exclusions)
+        };
+        let coverage_area = match coverage_basis {
+            CoverageBasis::Available => available.unsigned_area(),
+            CoverageBasis::LimitOfExcavation => limit_of_excavation.unsigned_area(),
+        };
+        Excavation {
+            available,
+            coverage_area,
+            centre_line: None,
+            focus: None,
+            principal_axis: principal_axis(limit_of_excavation),
+        }
+    }
+    pub fn with_centre_line(self, centre_line: Option<LineString>) -> Self {
+        Excavation {
+            centre_line,
+            ..self
+        }
+    }
+    pub fn with_focus(self, focus: Option<Point>) -> Self {
+        Excavation { focus, ..self }
+    }
+    // the coverage of the available area that gives a coverage of the coverage area
+    pub fn coverage_of_available(&self, coverage: Percentage) -> Percentage {
+        Percentage(coverage.0 * self.coverage_area / self.available.unsigned_area())
+    }
+    pub fn coverage_of_trenches(&self, structure: Structure, trenches: usize) -> Percentage {
+        let trench_area = structure.get_whole_trench_area(self);
+        Percentage((trenches as f64 * trench_area / self.coverage_area * 100.0).min(100.0))
+    }
+    pub fn principal_axis(&self) -> Option<Degree> {
+        self.principal_axis
+    }
+}
+
+// direction of the long side of the smallest rectangle around the polygons, in degrees
+// anticlockwise from east up to 180
+fn principal_axis(polygons: &MultiPolygon) -> Option<Degree> {
+    let rectangle = polygons.minimum_rotated_rect()?;
+    let corners = &rectangle.exterior().0;
+    if corners.len() < 3 {
+        return None;
+    }
+    let (side, end) = (corners[1] - corners[0], corners[2] - corners[1]);
+    let long_side = if side.x.hypot(side.y) >= end.x.hypot(end.y) {
+        side
+    } else {
+        end
+    };
+    Some(Degree(
+        long_side
+            .y
+            .atan2(long_side.x)
+            .to_degrees()
+            .rem_euclid(180.0),
+    ))
+}
+
+// the shape of a recorded feature, lines and points are widened when their size is recorded
+#[derive(Debug, Clone)]
+pub enum FeatureGeometry {
+    Polygon(MultiPolygon<f64>), // holes included
+    Line {
+        lines: MultiLineString<f64>, // such as ditches
+        buffer_width: Option<f64>,   // meters across the line
+    },
+    Point {
+        points: MultiPoint<f64>, // such as postholes
+        radius: Option<f64>,     // meters
+    },
+}
+
+impl FeatureGeometry {
+    // lines and points without a size have no area
+    pub fn area(&self) -> f64 {
+        match self {
+            FeatureGeometry::Polygon(polygons) => polygons.unsigned_area(),
+            FeatureGeometry::Line {
+                lines,
+                buffer_width,
+            } => lines.euclidean_length() * buffer_width.unwrap_or(0.0),
+            FeatureGeometry::Point { points, radius } => {
+                points.0.len() as f64 * std::f64::consts::PI * radius.unwrap_or(0.0).powi(2)
+            }
+        }
+    }
+}
+
+// a recorded feature with the attributes used to break down which kinds are missed
+#[derive(Debug, Clone)]
+pub struct ArchaeologicalFeature {
+    pub id: usize, // position in the features file, counting from 1
+    pub geometry: FeatureGeometry,
+    pub layer: Option<String>,
+    pub feature_type: Option<String>,
+    pub area: f64, // square meters
+}
+
+impl ArchaeologicalFeature {
+    pub fn new(
+        id: usize,
+        geometry: FeatureGeometry,
+        layer: Option<String>,
+        feature_type: Option<String>,
+    ) -> Self {
+        let area = geometry.area();
+        ArchaeologicalFeature {
+            id,
+            geometry,
+            layer,
+            feature_type,
+            area,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -44,6 +239,26 @@ impl Degree {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct Offset {
+    pub x: f64, // fraction of spacing
+    pub y: f64, // fraction of spacing
+}
+
+impl Offset {
+    pub fn new(x: f64, y: f64) -> Self {
+        Offset { x, y }
+    }
+}
+
+// where a layout was placed
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutPlacement {
+    pub rotation: Degree,
+    pub spacing: f64, // meters
+    pub offset: Offset,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Percentage(pub f64);
 
@@ -61,18 +276,100 @@ impl Percentage {
 
 pub mod array {
     use crate::Degree;
+    use serde::Deserialize;
+    use std::fmt;
+
     #[derive(Debug, Clone, Copy)]
     pub enum PatternRotationAxis {
         ByCell,
         ByColumn,
     }
 
+    // where the trench of each column and row goes, the columns a spacing apart
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Lattice {
+        #[default]
+        Square,
+        Staggered {
+            row_offset: f64,
+        }, // fraction of the spacing every other row is shifted along
+        Hexagonal, // staggered by half a spacing with rows closer, so each trench has six neighbours a spacing away
+        Rectangular {
+            row_spacing: f64,
+        }, // multiple of the spacing between rows
+    }
+
+    impl Lattice {
+        pub fn row_offset(self) -> f64 {
+            match self {
+                Lattice::Square | Lattice::Rectangular { .. } => 0.0,
+                Lattice::Staggered { row_offset } => row_offset.rem_euclid(1.0),
+                Lattice::Hexagonal => 0.5,
+            }
+        }
+        pub fn row_spacing(self) -> f64 {
+            match self {
+                Lattice::Square | Lattice::Staggered { .. } => 1.0,
+                Lattice::Hexagonal => 3.0_f64.sqrt() / 2.0,
+                Lattice::Rectangular { row_spacing } => row_spacing,
+            }
+        }
+        // rows after which the offsets repeat
+        pub fn row_period(self) -> i32 {
+            match self {
+                Lattice::Staggered { .. } => 2,
+                _ => 1,
+            }
+        }
+        // centre of a trench in spacings from the origin of the pattern
+        pub fn position(self, column: i32, row: i32) -> (f64, f64) {
+            let offset = if row.rem_euclid(2) == 1 {
+                self.row_offset()
+            } else {
+                0.0
+            };
+            (column as f64 + offset, row as f64 * self.row_spacing())
+        }
+    }
+
+    impl fmt::Display for Lattice {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Lattice::Square => write!(f, "square"),
+                Lattice::Staggered { row_offset } => write!(f, "staggered {}", row_offset),
+                Lattice::Hexagonal => write!(f, "hexagonal"),
+                Lattice::Rectangular { row_spacing } => write!(f, "rectangular {}", row_spacing),
+            }
+        }
+    }
+
     #[derive(Debug, Clone, Copy)]
     pub struct Configuration {
         pub base_angle: Degree,
         pub alternate_angle: Degree,
         pub pattern_rotation_axis: PatternRotationAxis,
         pub separated: bool,
+        pub lattice: Lattice,
+    }
+
+    impl Configuration {
+        // angle of the trench in a column and row, None where a separated array leaves a gap
+        pub fn angle_of_cell(&self, column: i32, row: i32) -> Option<Degree> {
+            let is_alternate_point = (column + row).rem_euclid(2) == 0;
+            if self.separated && is_alternate_point {
+                return None;
+            }
+            let is_base = match self.pattern_rotation_axis {
+                PatternRotationAxis::ByCell => is_alternate_point,
+                PatternRotationAxis::ByColumn => column.rem_euclid(2) == 0,
+            };
+            Some(if is_base {
+                self.base_angle
+            } else {
+                self.alternate_angle
+            })
+        }
     }
 }
 
@@ -80,14 +377,21 @@ pub mod array {
 pub enum Structure {
     Parallel(Line),
     Array(Rectangle, array::Configuration),
+    Spokes(Line), // radiating from the focus, a spacing apart where they reach farthest
+    Rings(Line),  // concentric around the focus, a spacing apart
 }
 
 impl Structure {
     pub fn get_rotational_symmetry(self) -> i32 {
         match self {
             Structure::Parallel(_) => 180,
+            // turning spokes is a shift of the offset and rings are unchanged by it
+            Structure::Spokes(_) | Structure::Rings(_) => 1,
             Structure::Array(rectangle, array_configuration) => {
-                if (rectangle.width == rectangle.length) && !array_configuration.separated {
+                if (rectangle.width == rectangle.length)
+                    && !array_configuration.separated
+                    && array_configuration.lattice == array::Lattice::Square
+                {
                     90
                 } else {
                     180
@@ -95,6 +399,75 @@ impl Structure {
             }
         }
     }
+    // coverage of an unbounded area by the pattern at a given spacing
+    pub fn get_coverage_from_spacing(self, spacing: f64) -> Percentage {
+        match self {
+            Structure::Parallel(line) | Structure::Rings(line) => {
+                Percentage::new_from_decimal(line.width / spacing)
+            }
+            // of the circle the spokes reach across, a spoke for each spacing round its edge
+            Structure::Spokes(line) => Percentage::new_from_decimal(2.0 * line.width / spacing),
+            Structure::Array(rectangle, array_configuration) => {
+                let row_spacing = array_configuration.lattice.row_spacing();
+                let coverage =
+                    (rectangle.width * rectangle.length) / (spacing * spacing * row_spacing);
+                if array_configuration.separated {
+                    // only every other cell holds a trench
+                    Percentage::new_from_decimal(coverage / 2.0)
+                } else {
+                    Percentage::new_from_decimal(coverage)
+                }
+            }
+        }
+    }
+    // continuous trenches and spokes run edge to edge so are always cut, and a ring's area does
+    // not say how big it was, so those cut are counted where they are laid out
+    pub fn is_clipped(self, trench_area: f64) -> bool {
+        match self {
+            Structure::Parallel(_) | Structure::Spokes(_) => true,
+            Structure::Rings(_) => false,
+            Structure::Array(rectangle, _) => {
+                trench_area < rectangle.width * rectangle.length * (1.0 - 1e-9)
+            }
+        }
+    }
+    // a continuous trench is taken to run the width of a square excavation
+    pub fn get_whole_trench_area(self, excavation: &Excavation) -> f64 {
+        let trench_length = match self {
+            Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => {
+                excavation.available.unsigned_area().sqrt()
+            }
+            Structure::Array(rectangle, _) => rectangle.length,
+        };
+        self.get_trench_width() * trench_length
+    }
+    pub fn get_trench_width(self) -> f64 {
+        match self {
+            Structure::Parallel(line) | Structure::Spokes(line) | Structure::Rings(line) => {
+                line.width
+            }
+            Structure::Array(rectangle, _) => rectangle.width,
+        }
+    }
+    // number of spacings in x and y after which the pattern repeats itself,
+    // parallel lines are unchanged by any shift along their length, and an x offset turns spokes
+    // or moves rings out by that fraction of the spacing
+    pub fn get_translational_symmetry(self) -> (f64, f64) {
+        match self {
+            Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => (1.0, 0.0),
+            Structure::Array(_, array_configuration) => {
+                let lattice = array_configuration.lattice;
+                let rows = lattice.row_period() as f64 * lattice.row_spacing();
+                if array_configuration.separated
+                    || array_configuration.base_angle.0 != array_configuration.alternate_angle.0
+                {
+                    (2.0, rows)
+                } else {
+                    (1.0, rows)
+                }
+            }
+        }
+    }
 }
 
 // TODO: add meters struct?
@@ -103,6 +476,258 @@ impl Structure {
 pub enum Distribution {
     Spacing(f64),         // meters
     Coverage(Percentage), // percentage coverage
+    Cost(f64),            // budget, dug as the coverage it pays for at each LOE
+    Count(usize),         // trenches, as the coverage of that many whole trenches on a lattice
+}
+
+// where trenches go, on a lattice swept through its rotations and offsets, along the centre line
+// of a corridor swept through its offsets, or scattered at random, each layout a seeded
+// realisation at a random rotation
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Placement {
+    #[default]
+    Lattice,
+    CentreLine, // columns along the alignment and rows across it, following its curves
+    Random {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_realisations")]
+        layouts: usize,
+    },
+    StratifiedRandom {
+        // one trench in each spacing-wide cell or strip of the lattice
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_realisations")]
+        layouts: usize,
+    },
+}
+
+// which way a layout on a lattice is turned, through every rotation of the pattern by default or
+// fixed to the principal axis of the LOE, with rotation 0 of a pattern running north
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    #[default]
+    Swept,
+    Aligned,       // trenches, or the columns of an array, running along the axis
+    Perpendicular, // running across it
+    Angle(f64),    // degrees anticlockwise from the axis
+    Auto,          // swept, with the layouts along the axis reported against the rest
+}
+
+impl Orientation {
+    // the whole degree a layout is turned to for an axis, None when every rotation is swept
+    pub fn rotation(self, axis: Degree, rotational_symmetry: i32) -> Option<i32> {
+        let angle = match self {
+            Orientation::Swept | Orientation::Auto => return None,
+            Orientation::Aligned => 0.0,
+            Orientation::Perpendicular => 90.0,
+            Orientation::Angle(angle) => angle,
+        };
+        Some(((axis.0 + angle - 90.0).round() as i32).rem_euclid(rotational_symmetry))
+    }
+}
+
+fn default_realisations() -> usize {
+    100
+}
+
+// what happens to trenches running over the edge of the LOE, an exclusion zone or the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClippingPolicy {
+    #[default]
+    Clip, // cut at the edge, however little is left
+    WholeOnly,          // drop any trench not entirely inside
+    MinimumLength(f64), // cut at the edge, dropping pieces shorter than this many meters
+    Shorten,            // shorten along the trench to where its full width fits
+}
+
+impl fmt::Display for ClippingPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClippingPolicy::Clip => write!(f, "clip"),
+            ClippingPolicy::WholeOnly => write!(f, "whole_only"),
+            ClippingPolicy::MinimumLength(minimum_length) => {
+                write!(f, "minimum_length {}m", minimum_length)
+            }
+            ClippingPolicy::Shorten => write!(f, "shorten"),
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Placement::Lattice => write!(f, "lattice"),
+            Placement::CentreLine => write!(f, "centre_line"),
+            Placement::Random { .. } => write!(f, "random"),
+            Placement::StratifiedRandom { .. } => write!(f, "stratified_random"),
+        }
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Orientation::Swept => write!(f, "swept"),
+            Orientation::Aligned => write!(f, "aligned"),
+            Orientation::Perpendicular => write!(f, "perpendicular"),
+            Orientation::Angle(angle) => write!(f, "angle {}", angle),
+            Orientation::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+// how closely a coverage is hit, by searching for the spacing between one too dense and one
+// too sparse
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CoverageSolver {
+    pub tolerance: f64, // percentage points either side of the target
+    pub max_iterations: usize,
+    pub adjust_length: bool, // shorten array trenches when no spacing lands within the tolerance
+    pub list_failures: bool, // print every layout missing the target, not only how many did
+}
+
+impl Default for CoverageSolver {
+    fn default() -> Self {
+        CoverageSolver {
+            tolerance: 0.05,
+            max_iterations: 40,
+            adjust_length: false,
+            list_failures: false,
+        }
+    }
+}
+
+// why a layout at a rotation and offset could not be given the target coverage
+#[derive(Debug, Clone, Copy)]
+pub enum CoverageFailure {
+    NothingAvailable,
+    Unattainable { maximum_coverage: f64 }, // at the minimum spacing
+    SingleTrenchExceeds { minimum_coverage: f64 },
+    NotConverged { closest_coverage: f64 }, // the coverage jumps over the target as trenches come and go
+}
+
+impl CoverageFailure {
+    // why, leaving out the coverage reached so failures can be counted by it
+    pub fn reason(&self) -> &'static str {
+        match self {
+            CoverageFailure::NothingAvailable => {
+                "Exclusion zones and the boundary buffer cover the LOE"
+            }
+            CoverageFailure::Unattainable { .. } => {
+                "Coverage is unattainable, at the minimum spacing the trenches cover"
+            }
+            CoverageFailure::SingleTrenchExceeds { .. } => {
+                "Coverage is too low, the sparsest layout covers"
+            }
+            CoverageFailure::NotConverged { .. } => {
+                "No spacing within the tolerance, the closest covers"
+            }
+        }
+    }
+    pub fn coverage(&self) -> Option<f64> {
+        match *self {
+            CoverageFailure::NothingAvailable => None,
+            CoverageFailure::Unattainable { maximum_coverage } => Some(maximum_coverage),
+            CoverageFailure::SingleTrenchExceeds { minimum_coverage } => Some(minimum_coverage),
+            CoverageFailure::NotConverged { closest_coverage } => Some(closest_coverage),
+        }
+    }
+}
+
+impl fmt::Display for CoverageFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.coverage() {
+            Some(coverage) => write!(f, "{} {:.2}%", self.reason(), coverage),
+            None => write!(f, "{}", self.reason()),
+        }
+    }
+}
+
+impl std::error::Error for CoverageFailure {}
+
+// what a percentage coverage is of when part of the LOE is excluded or kept clear of the boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageBasis {
+    #[default]
+    Available, // the LOE less its exclusion zones and boundary buffer
+    LimitOfExcavation, // the original LOE, as planning conditions give it
+}
+
+impl fmt::Display for CoverageBasis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CoverageBasis::Available => "available",
+            CoverageBasis::LimitOfExcavation => "limit_of_excavation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl TrenchLayout {
+    pub fn new(
+        trenches: MultiPolygon<f64>,
+        placement: &LayoutPlacement,
+        structure: Structure,
+        coverage: f64,
+    ) -> Self {
+        let metadata = LayoutMetadata::new(&trenches, placement, structure, coverage);
+        TrenchLayout {
+            trenches,
+            metadata: Some(metadata),
+        }
+    }
+}
+
+impl LayoutMetadata {
+    pub fn new(
+        trenches: &MultiPolygon<f64>,
+        placement: &LayoutPlacement,
+        structure: Structure,
+        coverage: f64,
+    ) -> Self {
+        let trench_width = structure.get_trench_width();
+        let total_area = trenches.unsigned_area();
+        LayoutMetadata {
+            rotation: placement.rotation.0,
+            spacing: placement.spacing,
+            x_offset: placement.offset.x,
+            y_offset: placement.offset.y,
+            trench_width,
+            coverage,
+            total_area,
+            total_length: total_area / trench_width,
+            trenches: trenches.0.len(),
+            clipped_trenches: trenches
+                .iter()
+                .filter(|trench| structure.is_clipped(trench.unsigned_area()))
+                .count(),
+        }
+    }
+}
+
+impl fmt::Display for LayoutMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} trenches ({} clipped), {:.1}m long and {:.1}m² covering {:.2}%, at {:.2}m spacing, rotation {:.1}, offset ({:.2}, {:.2})",
+            self.trenches,
+            self.clipped_trenches,
+            self.total_length,
+            self.total_area,
+            self.coverage,
+            self.spacing,
+            self.rotation,
+            self.x_offset,
+            self.y_offset
+        )
+    }
 }
 
 fn get_coords(rectangle: Rectangle, angle_from_verticle: Degree) -> [Coord; 4] {
@@ -139,11 +764,7 @@ fn get_index_of_coord_with_max_y(coords: [Coord; 4]) -> usize {
 fn is_coord_outside_x_bounds_of_line(coord: Coord, line: geo::Line<f64>) -> bool {
     // println!("line start: {:?}", line.start);
     // println!("line end: {:?}", line.end);
-    if coord.x < line.start.x || coord.x > line.end.x {
-        true
-    } else {
-        false
-    }
+    coord.x < line.start.x || coord.x > line.end.x
 }
 
 fn get_previous_and_next_index(i: usize, length: usize) -> (usize, usize) {
@@ -193,9 +814,55 @@ pub fn test_get_minimum_spacing(rectangle: Rectangle, angle_1: Degree, angle_2:
     println!("Minimum spacing: {:?}", min_spacing);
 }
 
+// trenches of every cell near those of a repeat of the pattern, each pair needing the spacing
+// that separates them along the line between their centres
+fn lattice_minimum_spacing(rectangle: Rectangle, array_configuration: array::Configuration) -> f64 {
+    let lattice = array_configuration.lattice;
+    let mut minimum_spacings = Vec::new();
+    for column in 0..2 {
+        for row in 0..lattice.row_period() * 2 {
+            let Some(angle) = array_configuration.angle_of_cell(column, row) else {
+                continue;
+            };
+            let (x, y) = lattice.position(column, row);
+            for other_column in column - 3..column + 4 {
+                for other_row in row - 3..row + 4 {
+                    let Some(other_angle) =
+                        array_configuration.angle_of_cell(other_column, other_row)
+                    else {
+                        continue;
+                    };
+                    if (other_column, other_row) == (column, row) {
+                        continue;
+                    }
+                    let (other_x, other_y) = lattice.position(other_column, other_row);
+                    let (dx, dy) = (other_x - x, other_y - y);
+                    // turning the pair so the line between them is vertical, minimum_spacing
+                    // measuring angles clockwise where trenches are turned anticlockwise
+                    let direction = Degree(90.0 - dy.atan2(dx).to_degrees());
+                    let separation = minimum_spacing(
+                        rectangle,
+                        Degree(-angle.add(direction).0),
+                        Degree(-other_angle.add(direction).0),
+                    );
+                    minimum_spacings.push(separation / dx.hypot(dy));
+                }
+            }
+        }
+    }
+    minimum_spacings
+        .iter()
+        .fold(f64::NEG_INFINITY, |max, &val| max.max(val))
+}
+
 pub fn get_minimum_spacing(structure: Structure) -> f64 {
     match structure {
-        Structure::Parallel(line) => return line.width,
+        Structure::Parallel(line) | Structure::Spokes(line) | Structure::Rings(line) => line.width,
+        Structure::Array(rectangle, array_configuration)
+            if array_configuration.lattice != array::Lattice::Square =>
+        {
+            lattice_minimum_spacing(rectangle, array_configuration)
+        }
         Structure::Array(rectangle, array_configuration) => {
             let horizontal_minimum_spacing = minimum_spacing(
                 rectangle,
@@ -215,13 +882,13 @@ pub fn get_minimum_spacing(structure: Structure) -> f64 {
                         array_configuration.base_angle.add(Degree(45.0)),
                     );
                     if array_configuration.separated {
-                        return diagonal_minimum_spacing
+                        diagonal_minimum_spacing
                             .max(verticle_minimum_spacing / 2.0)
-                            .max(horizontal_minimum_spacing / 2.0);
+                            .max(horizontal_minimum_spacing / 2.0)
                     } else {
-                        return diagonal_minimum_spacing
+                        diagonal_minimum_spacing
                             .max(verticle_minimum_spacing)
-                            .max(horizontal_minimum_spacing);
+                            .max(horizontal_minimum_spacing)
                     }
                 }
                 array::PatternRotationAxis::ByColumn => {
@@ -241,15 +908,15 @@ pub fn get_minimum_spacing(structure: Structure) -> f64 {
                         array_configuration.base_angle.add(Degree(45.0)),
                     );
                     if array_configuration.separated {
-                        return diagonal_minimum_spacing
+                        diagonal_minimum_spacing
                             .max(verticle_minimum_spacing_a / 2.0)
                             .max(verticle_minimum_spacing_b / 2.0)
-                            .max(horizontal_minimum_spacing / 2.0);
+                            .max(horizontal_minimum_spacing / 2.0)
                     } else {
-                        return diagonal_minimum_spacing
+                        diagonal_minimum_spacing
                             .max(verticle_minimum_spacing_a)
                             .max(verticle_minimum_spacing_b)
-                            .max(horizontal_minimum_spacing);
+                            .max(horizontal_minimum_spacing)
                     }
                 }
             }
@@ -257,36 +924,314 @@ pub fn get_minimum_spacing(structure: Structure) -> f64 {
     }
 }
 
+// the named layouts with a TrenchConfig constructor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrenchType {
+    Continuous,
+    ParallelArray,
+    StandardGrid,
+    TestPits,
+    Herringbone,
+    Spokes,
+    Rings,
+}
+
+impl FromStr for TrenchType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "continuous" => Ok(TrenchType::Continuous),
+            "parallel_array" => Ok(TrenchType::ParallelArray),
+            "standard_grid" => Ok(TrenchType::StandardGrid),
+            "test_pits" => Ok(TrenchType::TestPits),
+            "herringbone" => Ok(TrenchType::Herringbone),
+            "spokes" => Ok(TrenchType::Spokes),
+            "rings" => Ok(TrenchType::Rings),
+            _ => Err(anyhow!(
+                "Unknown trench type: {}, expected one of continuous, parallel_array, standard_grid, test_pits, herringbone, spokes or rings",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TrenchType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TrenchType::Continuous => "continuous",
+            TrenchType::ParallelArray => "parallel_array",
+            TrenchType::StandardGrid => "standard_grid",
+            TrenchType::TestPits => "test_pits",
+            TrenchType::Herringbone => "herringbone",
+            TrenchType::Spokes => "spokes",
+            TrenchType::Rings => "rings",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TrenchConfigError {
+    NonPositiveWidth(f64),
+    NonPositiveLength(f64),
+    NonPositiveRowSpacing(f64),
+    NonFiniteRowOffset(f64),
+    SpacingTooSmall {
+        spacing: f64,
+        minimum_spacing: f64,
+    },
+    CoverageOutOfRange(Percentage),
+    NonPositiveBudget(f64),
+    NoTrenches,
+    CoverageUnattainable {
+        coverage: Percentage,
+        maximum_coverage: Percentage,
+    },
+}
+
+impl fmt::Display for TrenchConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrenchConfigError::NonPositiveWidth(width) => {
+                write!(f, "Trench width must be positive, got {}m", width)
+            }
+            TrenchConfigError::NonPositiveLength(length) => {
+                write!(f, "Trench length must be positive, got {}m", length)
+            }
+            TrenchConfigError::NonPositiveRowSpacing(row_spacing) => write!(
+                f,
+                "Row spacing must be a positive multiple of the spacing, got {}",
+                row_spacing
+            ),
+            TrenchConfigError::NonFiniteRowOffset(row_offset) => write!(
+                f,
+                "Row offset must be a finite fraction of the spacing, got {}",
+                row_offset
+            ),
+            TrenchConfigError::SpacingTooSmall {
+                spacing,
+                minimum_spacing,
+            } => write!(
+                f,
+                "Spacing of {}m is too small, trenches overlap below {:.2}m",
+                spacing, minimum_spacing
+            ),
+            TrenchConfigError::CoverageOutOfRange(coverage) => write!(
+                f,
+                "Coverage must be greater than 0% and at most 100%, got {}%",
+                coverage.0
+            ),
+            TrenchConfigError::NonPositiveBudget(budget) => {
+                write!(
+                    f,
+                    "Budget must be a finite number greater than 0, got {}",
+                    budget
+                )
+            }
+            TrenchConfigError::NoTrenches => write!(f, "At least one trench must be placed"),
+            TrenchConfigError::CoverageUnattainable {
+                coverage,
+                maximum_coverage,
+            } => write!(
+                f,
+                "Coverage of {}% is unattainable, the trenches cover at most {:.2}%",
+                coverage.0, maximum_coverage.0
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TrenchConfigError {}
+
 #[derive(Debug, Clone, Copy)]
 pub struct TrenchConfig {
-    // TODO: add shifts in x/y
     pub structure: Structure,
     pub distribution: Distribution,
     pub minimum_spacing: f64,
+    pub offset_steps: i32, // x/y shifts of the pattern tested across one repeat of the pattern
+    pub coverage_basis: CoverageBasis,
+    pub boundary_buffer: f64, // meters trenches are kept inside the LOE edge and its holes
+    pub clipping: ClippingPolicy,
+    pub solver: CoverageSolver,
+    pub cost: CostModel,
+    pub placement: Placement,
+    pub orientation: Orientation,
 }
 
 impl TrenchConfig {
-    // TODO: add centre_line
-    // TODO: add validate_spacing
-    pub fn validate_spacing(minimum_spacing: f64, distribution: Distribution) {
+    fn validate_structure(structure: Structure) -> Result<(), TrenchConfigError> {
+        let (width, length) = match structure {
+            Structure::Parallel(line) | Structure::Spokes(line) | Structure::Rings(line) => {
+                (line.width, None)
+            }
+            Structure::Array(rectangle, array_configuration) => {
+                let row_spacing = array_configuration.lattice.row_spacing();
+                if !row_spacing.is_finite() || row_spacing <= 0.0 {
+                    return Err(TrenchConfigError::NonPositiveRowSpacing(row_spacing));
+                }
+                if let array::Lattice::Staggered { row_offset } = array_configuration.lattice {
+                    if !row_offset.is_finite() {
+                        return Err(TrenchConfigError::NonFiniteRowOffset(row_offset));
+                    }
+                }
+                (rectangle.width, Some(rectangle.length))
+            }
+        };
+        // comparisons with NaN are false, so non-finite values are rejected explicitly
+        if !width.is_finite() || width <= 0.0 {
+            return Err(TrenchConfigError::NonPositiveWidth(width));
+        }
+        match length {
+            Some(length) if !length.is_finite() || length <= 0.0 => {
+                Err(TrenchConfigError::NonPositiveLength(length))
+            }
+            _ => Ok(()),
+        }
+    }
+    fn validate_distribution(
+        structure: Structure,
+        minimum_spacing: f64,
+        distribution: Distribution,
+    ) -> Result<(), TrenchConfigError> {
         match distribution {
             Distribution::Spacing(spacing) => {
-                assert!(minimum_spacing < spacing, "Spacing too small");
+                if !spacing.is_finite() || spacing <= minimum_spacing {
+                    return Err(TrenchConfigError::SpacingTooSmall {
+                        spacing,
+                        minimum_spacing,
+                    });
+                }
+            }
+            Distribution::Coverage(coverage) => {
+                if !coverage.0.is_finite() || coverage.0 <= 0.0 || coverage.0 > 100.0 {
+                    return Err(TrenchConfigError::CoverageOutOfRange(coverage));
+                }
+                let maximum_coverage = structure.get_coverage_from_spacing(minimum_spacing);
+                if coverage.0 > maximum_coverage.0 {
+                    return Err(TrenchConfigError::CoverageUnattainable {
+                        coverage,
+                        maximum_coverage,
+                    });
+                }
+            }
+            Distribution::Cost(budget) => {
+                if !budget.is_finite() || budget <= 0.0 {
+                    return Err(TrenchConfigError::NonPositiveBudget(budget));
+                }
+            }
+            Distribution::Count(trenches) => {
+                if trenches == 0 {
+                    return Err(TrenchConfigError::NoTrenches);
+                }
             }
-            _ => {}
         }
+        Ok(())
     }
-    pub fn continuous(width: f64, distribution: Distribution) -> Self {
-        let structure = Structure::Parallel(Line { width });
+    pub fn new(
+        structure: Structure,
+        distribution: Distribution,
+    ) -> Result<Self, TrenchConfigError> {
+        Self::validate_structure(structure)?;
         let minimum_spacing = get_minimum_spacing(structure);
-        Self::validate_spacing(minimum_spacing, distribution);
-        TrenchConfig {
+        Self::validate_distribution(structure, minimum_spacing, distribution)?;
+        Ok(TrenchConfig {
             structure,
             distribution,
             minimum_spacing,
+            offset_steps: 1,
+            coverage_basis: CoverageBasis::default(),
+            boundary_buffer: 0.0,
+            clipping: ClippingPolicy::default(),
+            solver: CoverageSolver::default(),
+            cost: CostModel::default(),
+            placement: Placement::default(),
+            orientation: Orientation::default(),
+        })
+    }
+    pub fn with_offset_steps(self, offset_steps: i32) -> Self {
+        TrenchConfig {
+            offset_steps: offset_steps.max(1),
+            ..self
+        }
+    }
+    pub fn with_coverage_basis(self, coverage_basis: CoverageBasis) -> Self {
+        TrenchConfig {
+            coverage_basis,
+            ..self
+        }
+    }
+    pub fn with_boundary_buffer(self, boundary_buffer: f64) -> Self {
+        TrenchConfig {
+            boundary_buffer: boundary_buffer.max(0.0),
+            ..self
         }
     }
-    pub fn parallel_array(width: f64, length: f64, distribution: Distribution) -> Self {
+    pub fn with_clipping(self, clipping: ClippingPolicy) -> Self {
+        TrenchConfig { clipping, ..self }
+    }
+    pub fn with_solver(self, solver: CoverageSolver) -> Self {
+        TrenchConfig { solver, ..self }
+    }
+    pub fn with_cost_model(self, cost: CostModel) -> Self {
+        TrenchConfig { cost, ..self }
+    }
+    pub fn with_placement(self, placement: Placement) -> Self {
+        TrenchConfig { placement, ..self }
+    }
+    pub fn with_orientation(self, orientation: Orientation) -> Self {
+        TrenchConfig {
+            orientation,
+            ..self
+        }
+    }
+    // whole degrees the pattern is turned to, every one up to its symmetry unless the
+    // orientation fixes one to the principal axis of the excavation
+    pub fn get_rotations(&self, excavation: &Excavation) -> Vec<i32> {
+        match self.get_fixed_rotation(excavation) {
+            Some(rotation) => vec![rotation],
+            None => (0..self.structure.get_rotational_symmetry()).collect(),
+        }
+    }
+    pub fn get_fixed_rotation(&self, excavation: &Excavation) -> Option<i32> {
+        self.orientation.rotation(
+            excavation.principal_axis()?,
+            self.structure.get_rotational_symmetry(),
+        )
+    }
+    // the rotation running along the principal axis, which auto orientation reports on
+    pub fn get_aligned_rotation(&self, excavation: &Excavation) -> Option<i32> {
+        Orientation::Aligned.rotation(
+            excavation.principal_axis()?,
+            self.structure.get_rotational_symmetry(),
+        )
+    }
+    pub fn get_offsets(&self) -> Vec<Offset> {
+        let (x_period, y_period) = self.structure.get_translational_symmetry();
+        let steps = self.offset_steps;
+        let y_steps = if y_period == 0.0 { 1 } else { steps };
+        (0..steps)
+            .flat_map(|x| {
+                (0..y_steps).map(move |y| {
+                    Offset::new(
+                        x as f64 * x_period / steps as f64,
+                        y as f64 * y_period / steps as f64,
+                    )
+                })
+            })
+            .collect()
+    }
+    pub fn continuous(width: f64, distribution: Distribution) -> Result<Self, TrenchConfigError> {
+        let structure = Structure::Parallel(Line { width });
+        Self::new(structure, distribution)
+    }
+    pub fn parallel_array(
+        width: f64,
+        length: f64,
+        lattice: array::Lattice,
+        distribution: Distribution,
+    ) -> Result<Self, TrenchConfigError> {
         let structure = Structure::Array(
             Rectangle { width, length },
             array::Configuration {
@@ -294,17 +1239,17 @@ impl TrenchConfig {
                 alternate_angle: Degree::new(0.0),
                 pattern_rotation_axis: array::PatternRotationAxis::ByCell,
                 separated: true,
+                lattice,
             },
         );
-        let minimum_spacing = get_minimum_spacing(structure);
-        Self::validate_spacing(minimum_spacing, distribution);
-        TrenchConfig {
-            structure,
-            distribution,
-            minimum_spacing,
-        }
+        Self::new(structure, distribution)
     }
-    pub fn standard_grid(width: f64, length: f64, distribution: Distribution) -> Self {
+    pub fn standard_grid(
+        width: f64,
+        length: f64,
+        lattice: array::Lattice,
+        distribution: Distribution,
+    ) -> Result<Self, TrenchConfigError> {
         let structure = Structure::Array(
             Rectangle { width, length },
             array::Configuration {
@@ -312,17 +1257,16 @@ impl TrenchConfig {
                 alternate_angle: Degree::new(90.0),
                 pattern_rotation_axis: array::PatternRotationAxis::ByCell,
                 separated: false,
+                lattice,
             },
         );
-        let minimum_spacing = get_minimum_spacing(structure);
-        Self::validate_spacing(minimum_spacing, distribution);
-        TrenchConfig {
-            structure,
-            distribution,
-            minimum_spacing,
-        }
+        Self::new(structure, distribution)
     }
-    pub fn test_pits(width: f64, distribution: Distribution) -> Self {
+    pub fn test_pits(
+        width: f64,
+        lattice: array::Lattice,
+        distribution: Distribution,
+    ) -> Result<Self, TrenchConfigError> {
         let structure = Structure::Array(
             Rectangle {
                 width,
@@ -333,17 +1277,17 @@ impl TrenchConfig {
                 alternate_angle: Degree::new(0.0),
                 pattern_rotation_axis: array::PatternRotationAxis::ByCell,
                 separated: false,
+                lattice,
             },
         );
-        let minimum_spacing = get_minimum_spacing(structure);
-        Self::validate_spacing(minimum_spacing, distribution);
-        TrenchConfig {
-            structure,
-            distribution,
-            minimum_spacing,
-        }
+        Self::new(structure, distribution)
     }
-    pub fn herringbone(width: f64, length: f64, distribution: Distribution) -> Self {
+    pub fn herringbone(
+        width: f64,
+        length: f64,
+        lattice: array::Lattice,
+        distribution: Distribution,
+    ) -> Result<Self, TrenchConfigError> {
         let structure = Structure::Array(
             Rectangle { width, length },
             array::Configuration {
@@ -351,44 +1295,68 @@ impl TrenchConfig {
                 alternate_angle: Degree::new(315.0),
                 pattern_rotation_axis: array::PatternRotationAxis::ByColumn,
                 separated: false,
+                lattice,
             },
         );
-        let minimum_spacing = get_minimum_spacing(structure);
-        Self::validate_spacing(minimum_spacing, distribution);
-        TrenchConfig {
-            structure,
-            distribution,
-            minimum_spacing,
+        Self::new(structure, distribution)
+    }
+    pub fn spokes(width: f64, distribution: Distribution) -> Result<Self, TrenchConfigError> {
+        let structure = Structure::Spokes(Line { width });
+        Self::new(structure, distribution)
+    }
+    pub fn rings(width: f64, distribution: Distribution) -> Result<Self, TrenchConfigError> {
+        let structure = Structure::Rings(Line { width });
+        Self::new(structure, distribution)
+    }
+    // length is ignored by the trench types without one, and the lattice by continuous trenches,
+    // spokes and rings
+    pub fn of_type(
+        trench_type: TrenchType,
+        width: f64,
+        length: f64,
+        lattice: array::Lattice,
+        distribution: Distribution,
+    ) -> Result<Self, TrenchConfigError> {
+        match trench_type {
+            TrenchType::Continuous => Self::continuous(width, distribution),
+            TrenchType::ParallelArray => Self::parallel_array(width, length, lattice, distribution),
+            TrenchType::StandardGrid => Self::standard_grid(width, length, lattice, distribution),
+            TrenchType::TestPits => Self::test_pits(width, lattice, distribution),
+            TrenchType::Herringbone => Self::herringbone(width, length, lattice, distribution),
+            TrenchType::Spokes => Self::spokes(width, distribution),
+            TrenchType::Rings => Self::rings(width, distribution),
         }
     }
-    // pub fn centre_line_of_width(width: f64) -> Self {
-    //     TrenchConfig {
-    //         structure: Structure::Parallel(Line { width }),
-    //         distribution: Distribution::Spacing(0.0),
-    //     }
-    // }
-
-    // pub fn centre_line_coverage(coverage: f64) -> Self {
-    //     TrenchConfig {
-    //         structure: Structure::Parallel(Line { width: 0.0 }),
-    //         distribution: Distribution::Coverage(coverage),
-    //     }
-    // }
 }
 
 pub fn read_single_test_location_data(
+    catalogue: &SiteCatalogue,
     site_name: String,
     loe_i: String,
     selected_layer: Option<&str>,
 ) -> Result<TestLocation> {
     let now = Instant::now();
-    let limit_of_excavation = read_single_loe_feature(site_name.clone(), loe_i.clone())?;
-    let gj = read_single_features_geojson(site_name.clone(), loe_i.clone())?;
+    let site = catalogue.site(&site_name);
+    let period = site.and_then(|site| site.period.clone());
+    let geology = site.and_then(|site| site.geology.clone());
+    let location_path = catalogue.location_path(&site_name, &loe_i);
+    let limit_of_excavation = read_single_loe_feature(&location_path)?;
+    let exclusions = read_single_exclusions(&location_path)?;
+    let centre_line = read_single_centre_line(&location_path)?;
+    let focus = read_single_focus(&location_path)?;
+    let gj = read_single_features_geojson(&location_path)?;
     match process_geojson(&gj, selected_layer) {
         Some(features) => {
             println!("Reading files took: {:?}", now.elapsed());
             Ok(TestLocation {
+                site_name,
+                loe_i,
+                period,
+                geology,
                 limit_of_excavation,
+                exclusions,
+                centre_line,
+                focus,
                 features,
             })
         }
@@ -401,72 +1369,261 @@ pub fn read_single_test_location_data(
     }
 }
 
-fn read_single_features_geojson(site_name: String, loe_i: String) -> Result<GeoJson> {
-    let file = File::open(format!(
-        "../data/grouped_by_loe/{}/{}/features.geojson",
-        site_name, loe_i
-    ))?;
+fn read_single_features_geojson(location_path: &Path) -> Result<GeoJson> {
+    read_geojson(&location_path.join("features.geojson"))
+}
+
+fn read_geojson(path: &Path) -> Result<GeoJson> {
+    let file = File::open(path)?;
     let reader = BufReader::new(file);
     let gj: GeoJson = serde_json::from_reader(reader)?;
     Ok(gj)
 }
 
-fn get_site_outline_of_loe(polygon: Vec<Vec<Vec<f64>>>) -> Polygon {
-    if polygon.len() > 1 {
-        println!("Warning: more than one polygon found for LOE");
+pub fn read_features(
+    features_path: &Path,
+    selected_layer: Option<&str>,
+) -> Result<Vec<ArchaeologicalFeature>> {
+    let gj = read_geojson(features_path)?;
+    process_geojson(&gj, selected_layer)
+        .ok_or_else(|| anyhow!("No {:?} features in {:?}", selected_layer, features_path))
+}
+
+// polygons and multipolygons from a Feature, FeatureCollection or bare Geometry
+pub fn read_trench_layout(layout_path: &Path) -> Result<TrenchLayout> {
+    let (geometries, metadata): (Vec<Geometry>, Option<LayoutMetadata>) =
+        match read_geojson(layout_path)? {
+            GeoJson::FeatureCollection(collection) => {
+                // saved by write_trench_layout, other GIS may leave it out
+                let metadata = collection
+                    .foreign_members
+                    .as_ref()
+                    .and_then(|members| members.get("layout"))
+                    .map(|layout| serde_json::from_value(layout.clone()))
+                    .transpose()?;
+                let geometries = collection
+                    .features
+                    .into_iter()
+                    .filter_map(|feature| feature.geometry)
+                    .collect();
+                (geometries, metadata)
+            }
+            GeoJson::Feature(feature) => (feature.geometry.into_iter().collect(), None),
+            GeoJson::Geometry(geometry) => (vec![geometry], None),
+        };
+    let mut trenches = Vec::new();
+    for geometry in geometries {
+        match geo::Geometry::<f64>::try_from(geometry)? {
+            geo::Geometry::Polygon(polygon) => trenches.push(polygon),
+            geo::Geometry::MultiPolygon(multi_polygon) => trenches.extend(multi_polygon),
+            _ => return Err(anyhow!("Trench layouts can only contain polygons")),
+        }
     }
-    let poly_exterior = polygon[0]
+    Ok(TrenchLayout {
+        trenches: MultiPolygon(trenches),
+        metadata,
+    })
+}
+
+// each trench is saved as its own feature, with its placement so the layout can be reviewed in GIS
+pub fn write_trench_layout(trench_layout: &TrenchLayout, layout_path: &Path) -> Result<()> {
+    let features = trench_layout
+        .trenches
         .iter()
-        .map(|c| {
-            coord! { x: c[0], y: c[1] }
+        .enumerate()
+        .map(|(i, trench)| {
+            let area = trench.unsigned_area();
+            let mut properties = JsonObject::new();
+            properties.insert("trench_id".to_string(), JsonValue::from(i + 1));
+            if let Some(metadata) = trench_layout.metadata {
+                properties.insert("rotation".to_string(), JsonValue::from(metadata.rotation));
+                properties.insert("spacing".to_string(), JsonValue::from(metadata.spacing));
+                properties.insert("x_offset".to_string(), JsonValue::from(metadata.x_offset));
+                properties.insert("y_offset".to_string(), JsonValue::from(metadata.y_offset));
+                properties.insert("width".to_string(), JsonValue::from(metadata.trench_width));
+                // length left once cut to the LOE, exact for trenches cut square across
+                properties.insert(
+                    "clipped_length".to_string(),
+                    JsonValue::from(area / metadata.trench_width),
+                );
+            }
+            properties.insert("area".to_string(), JsonValue::from(area));
+            Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(Value::from(trench))),
+                id: Some(Id::Number((i + 1).into())),
+                properties: Some(properties),
+                foreign_members: None,
+            }
         })
         .collect();
-    Polygon::new(LineString(poly_exterior), vec![])
+    let foreign_members = match trench_layout.metadata {
+        Some(metadata) => {
+            let mut members = JsonObject::new();
+            members.insert("layout".to_string(), serde_json::to_value(metadata)?);
+            Some(members)
+        }
+        None => None,
+    };
+    let feature_collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members,
+    };
+    let writer = BufWriter::new(File::create(layout_path)?);
+    serde_json::to_writer(writer, &GeoJson::FeatureCollection(feature_collection))?;
+    Ok(())
 }
 
-fn read_single_loe_feature(site_name: String, loe_i: String) -> Result<Polygon> {
-    let file = File::open(format!(
-        "../data/grouped_by_loe/{}/{}/loe.geojson",
-        site_name, loe_i
-    ))?;
+fn read_single_loe_feature(location_path: &Path) -> Result<MultiPolygon> {
+    read_limit_of_excavation(&location_path.join("loe.geojson"))
+}
+
+// a polygon or multipolygon, holes included
+pub fn read_limit_of_excavation(loe_path: &Path) -> Result<MultiPolygon> {
+    let file = File::open(loe_path)?;
     let reader = BufReader::new(file);
     let feature: Feature = serde_json::from_reader(reader)?;
     match feature.geometry {
-        Some(geometry) => match geometry.value {
-            Value::Polygon(polygon) => Ok(get_site_outline_of_loe(polygon)),
-            _ => {
-                return Err(anyhow!("Geometry is not a polygon"));
-            }
+        Some(geometry) => match geo::Geometry::<f64>::try_from(geometry)? {
+            geo::Geometry::Polygon(polygon) => Ok(MultiPolygon(vec![polygon])),
+            geo::Geometry::MultiPolygon(multi_polygon) => Ok(multi_polygon),
+            _ => Err(anyhow!("Geometry is not a polygon or multipolygon")),
         },
         // Ok(geometry),
         None => Err(anyhow!("No geometry found in LOE file")),
     }
 }
 
-pub fn read_all_test_location_data(selected_layer: Option<&str>) -> Result<Vec<TestLocation>> {
+// exclusion zones are optional, a location without an exclusions.geojson has none
+fn read_single_exclusions(location_path: &Path) -> Result<MultiPolygon> {
+    let exclusions_path = location_path.join("exclusions.geojson");
+    if exclusions_path.exists() {
+        read_exclusions(&exclusions_path)
+    } else {
+        Ok(MultiPolygon(vec![]))
+    }
+}
+
+// every polygon in a Feature or FeatureCollection, merged so overlapping zones are only counted once
+pub fn read_exclusions(exclusions_path: &Path) -> Result<MultiPolygon> {
+    let geometries: Vec<Geometry> = match read_geojson(exclusions_path)? {
+        GeoJson::FeatureCollection(collection) => collection
+            .features
+            .into_iter()
+            .filter_map(|feature| feature.geometry)
+            .collect(),
+        GeoJson::Feature(feature) => feature.geometry.into_iter().collect(),
+        GeoJson::Geometry(geometry) => vec![geometry],
+    };
+    let mut exclusions = MultiPolygon(vec![]);
+    for geometry in geometries {
+        let polygons = match geo::Geometry::<f64>::try_from(geometry)? {
+            geo::Geometry::Polygon(polygon) => MultiPolygon(vec![polygon]),
+            geo::Geometry::MultiPolygon(multi_polygon) => multi_polygon,
+            _ => return Err(anyhow!("Exclusion zones must be polygons or multipolygons")),
+        };
+        exclusions = exclusions.union(&polygons);
+    }
+    Ok(exclusions)
+}
+
+// the centre line is optional, a location without a centre_line.geojson has it found from the LOE
+fn read_single_centre_line(location_path: &Path) -> Result<Option<LineString>> {
+    let centre_line_path = location_path.join("centre_line.geojson");
+    if centre_line_path.exists() {
+        read_centre_line(&centre_line_path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+// a single line in a Feature, FeatureCollection or bare geometry
+pub fn read_centre_line(centre_line_path: &Path) -> Result<LineString> {
+    let geometries: Vec<Geometry> = match read_geojson(centre_line_path)? {
+        GeoJson::FeatureCollection(collection) => collection
+            .features
+            .into_iter()
+            .filter_map(|feature| feature.geometry)
+            .collect(),
+        GeoJson::Feature(feature) => feature.geometry.into_iter().collect(),
+        GeoJson::Geometry(geometry) => vec![geometry],
+    };
+    let [geometry] = <[Geometry; 1]>::try_from(geometries)
+        .map_err(|_| anyhow!("Centre line file {:?} must hold one line", centre_line_path))?;
+    match geo::Geometry::<f64>::try_from(geometry)? {
+        geo::Geometry::LineString(line) => Ok(line),
+        geo::Geometry::MultiLineString(MultiLineString(mut lines)) if lines.len() == 1 => {
+            Ok(lines.remove(0))
+        }
+        _ => Err(anyhow!("Centre line must be a single line")),
+    }
+}
+
+// the focus is optional, spokes and rings at a location without a focus.geojson are centred on
+// the LOE
+fn read_single_focus(location_path: &Path) -> Result<Option<Point>> {
+    let focus_path = location_path.join("focus.geojson");
+    if focus_path.exists() {
+        read_focus(&focus_path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+// a single point in a Feature, FeatureCollection or bare geometry
+pub fn read_focus(focus_path: &Path) -> Result<Point> {
+    let geometries: Vec<Geometry> = match read_geojson(focus_path)? {
+        GeoJson::FeatureCollection(collection) => collection
+            .features
+            .into_iter()
+            .filter_map(|feature| feature.geometry)
+            .collect(),
+        GeoJson::Feature(feature) => feature.geometry.into_iter().collect(),
+        GeoJson::Geometry(geometry) => vec![geometry],
+    };
+    let [geometry] = <[Geometry; 1]>::try_from(geometries)
+        .map_err(|_| anyhow!("Focus file {:?} must hold one point", focus_path))?;
+    match geo::Geometry::<f64>::try_from(geometry)? {
+        geo::Geometry::Point(point) => Ok(point),
+        geo::Geometry::MultiPoint(MultiPoint(mut points)) if points.len() == 1 => {
+            Ok(points.remove(0))
+        }
+        _ => Err(anyhow!("Focus must be a single point")),
+    }
+}
+
+pub fn read_all_test_location_data(
+    catalogue: &SiteCatalogue,
+    selected_layer: Option<&str>,
+) -> Result<Vec<TestLocation>> {
     let now = Instant::now();
     let mut test_locations = Vec::new();
 
-    let sites_location_counts = [
-        ("Stansted", 17),
-        // ("Heathrow", 5),
-        ("A355_BeaconsfieldEasternReliefRoad", 3),
-        ("_NDR__", 22),
-        ("wingerworth", 2),
-    ];
-    for (site, location_count) in sites_location_counts.iter() {
-        for i in 0..*location_count {
-            let limit_of_excavation = read_single_loe_feature(site.to_string(), i.to_string())?;
-            let features = read_single_features_geojson(site.to_string(), i.to_string())?;
+    for site in catalogue.sites.iter() {
+        for loe_i in site.loes.iter() {
+            let location_path = catalogue.location_path(&site.name, loe_i);
+            let limit_of_excavation = read_single_loe_feature(&location_path)?;
+            let exclusions = read_single_exclusions(&location_path)?;
+            let centre_line = read_single_centre_line(&location_path)?;
+            let focus = read_single_focus(&location_path)?;
+            let features = read_single_features_geojson(&location_path)?;
             match process_geojson(&features, selected_layer) {
-                Some(polygons) => {
+                Some(features) => {
                     test_locations.push(TestLocation {
-                        limit_of_excavation: limit_of_excavation,
-                        features: polygons,
+                        site_name: site.name.clone(),
+                        loe_i: loe_i.clone(),
+                        period: site.period.clone(),
+                        geology: site.geology.clone(),
+                        limit_of_excavation,
+                        exclusions,
+                        centre_line,
+                        focus,
+                        features,
                     });
                 }
                 None => {
-                    // println!("Unable to make polygons for site: {} location: {}", site, i);
+                    // println!("Unable to make polygons for site: {} location: {}", site.name, loe_i);
                 }
             }
         }
@@ -475,25 +1632,30 @@ pub fn read_all_test_location_data(selected_layer: Option<&str>) -> Result<Vec<T
     Ok(test_locations)
 }
 
-fn process_geojson(gj: &GeoJson, selected_layer: Option<&str>) -> Option<Vec<Polygon<f64>>> {
+fn process_geojson(
+    gj: &GeoJson,
+    selected_layer: Option<&str>,
+) -> Option<Vec<ArchaeologicalFeature>> {
     match *gj {
         GeoJson::FeatureCollection(ref collection) => {
             let mut polygons = Vec::new();
-            for feature in &collection.features {
-                match selected_layer {
-                    // Skip features that don't match the selected layer
-                    Some(layer) => {
-                        if feature.property("Layer").unwrap() != layer {
-                            continue;
-                        }
+            for (i, feature) in collection.features.iter().enumerate() {
+                // Skip features that don't match the selected layer, or have no layer
+                if let Some(layer) = selected_layer {
+                    if string_property(feature, "Layer").as_deref() != Some(layer) {
+                        continue;
                     }
-                    None => {}
                 }
                 if let Some(ref geom) = feature.geometry {
-                    if let Some(poly) = geometry_to_polygon(geom) {
-                        polygons.push(poly);
+                    if let Some(geometry) = geometry_to_feature_geometry(geom, feature) {
+                        polygons.push(ArchaeologicalFeature::new(
+                            i + 1,
+                            geometry,
+                            string_property(feature, "Layer"),
+                            string_property(feature, "Type"),
+                        ));
                     } else {
-                        println!("No polygon found");
+                        println!("No polygon, line or point found");
                     }
                 }
             }
@@ -510,22 +1672,339 @@ fn process_geojson(gj: &GeoJson, selected_layer: Option<&str>) -> Option<Vec<Pol
     }
 }
 
-// Process GeoJSON geometries
-fn geometry_to_polygon(geom: &Geometry) -> Option<Polygon<f64>> {
-    match geom.value {
-        Value::Polygon(ref polygon) => {
-            let poly_exterior = polygon[0]
-                .iter()
-                .map(|c| {
-                    coord! { x: c[0], y: c[1] }
-                })
-                .collect();
-            Some(Polygon::new(LineString(poly_exterior), vec![]))
+fn string_property(feature: &Feature, name: &str) -> Option<String> {
+    feature
+        .property(name)
+        .and_then(|value| value.as_str())
+        .map(|value| value.to_string())
+}
+
+fn number_property(feature: &Feature, name: &str) -> Option<f64> {
+    feature.property(name).and_then(|value| value.as_f64())
+}
+
+// Process GeoJSON geometries, lines and points are sized by buffer_width and radius properties
+fn geometry_to_feature_geometry(geom: &Geometry, feature: &Feature) -> Option<FeatureGeometry> {
+    match geo::Geometry::<f64>::try_from(geom.clone()).ok()? {
+        geo::Geometry::Polygon(polygon) => {
+            Some(FeatureGeometry::Polygon(MultiPolygon(vec![polygon])))
         }
+        geo::Geometry::MultiPolygon(multi_polygon) => Some(FeatureGeometry::Polygon(multi_polygon)),
+        geo::Geometry::LineString(line_string) => Some(FeatureGeometry::Line {
+            lines: MultiLineString(vec![line_string]),
+            buffer_width: number_property(feature, "buffer_width"),
+        }),
+        geo::Geometry::MultiLineString(lines) => Some(FeatureGeometry::Line {
+            lines,
+            buffer_width: number_property(feature, "buffer_width"),
+        }),
+        geo::Geometry::Point(point) => Some(FeatureGeometry::Point {
+            points: MultiPoint(vec![point]),
+            radius: number_property(feature, "radius"),
+        }),
+        geo::Geometry::MultiPoint(points) => Some(FeatureGeometry::Point {
+            points,
+            radius: number_property(feature, "radius"),
+        }),
         _ => {
-            // TODO: update this placeholder for other geometry types
             println!("Matched some other geometry");
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{Polygon, Rect};
+
+    #[test]
+    fn spacing_must_be_finite_and_clear_of_the_minimum() {
+        for spacing in [f64::NAN, 1.0] {
+            assert!(matches!(
+                TrenchConfig::continuous(2.0, Distribution::Spacing(spacing)),
+                Err(TrenchConfigError::SpacingTooSmall { .. })
+            ));
+        }
+        assert!(TrenchConfig::continuous(2.0, Distribution::Spacing(20.0)).is_ok());
+    }
+
+    #[test]
+    fn budget_must_be_finite_and_positive() {
+        for budget in [f64::NAN, f64::INFINITY, 0.0] {
+            assert!(matches!(
+                TrenchConfig::continuous(2.0, Distribution::Cost(budget)),
+                Err(TrenchConfigError::NonPositiveBudget(_))
+            ));
+        }
+        assert!(TrenchConfig::continuous(2.0, Distribution::Cost(20000.0)).is_ok());
+    }
+
+    #[test]
+    fn lattice_row_offset_and_spacing_must_be_finite() {
+        let grid =
+            |lattice| TrenchConfig::standard_grid(2.0, 30.0, lattice, Distribution::Spacing(60.0));
+        for row_offset in [f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                grid(array::Lattice::Staggered { row_offset }),
+                Err(TrenchConfigError::NonFiniteRowOffset(_))
+            ));
+        }
+        for row_spacing in [f64::NAN, 0.0] {
+            assert!(matches!(
+                grid(array::Lattice::Rectangular { row_spacing }),
+                Err(TrenchConfigError::NonPositiveRowSpacing(_))
+            ));
+        }
+        assert!(grid(array::Lattice::Staggered { row_offset: 1.5 }).is_ok());
+    }
+
+    #[test]
+    fn features_without_a_layer_are_skipped_when_one_is_selected() {
+        let gj: GeoJson = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "Layer": "Roman", "Type": "pit" },
+                    "geometry": { "type": "Point", "coordinates": [0.0, 0.0] }
+                },
+                {
+                    "type": "Feature",
+                    "properties": { "Type": "ditch" },
+                    "geometry": { "type": "Point", "coordinates": [1.0, 1.0] }
+                }
+            ]
+        }"#
+        .parse()
+        .unwrap();
+        let features = process_geojson(&gj, Some("Roman")).unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].layer.as_deref(), Some("Roman"));
+        assert_eq!(process_geojson(&gj, None).unwrap().len(), 2);
+        assert!(process_geojson(&gj, Some("Saxon")).is_none());
+    }
+
+    fn rectangle(min: (f64, f64), max: (f64, f64)) -> Polygon {
+        Rect::new(coord! { x: min.0, y: min.1 }, coord! { x: max.0, y: max.1 }).to_polygon()
+    }
+
+    #[test]
+    fn principal_axis_follows_the_loe_whatever_is_excluded() {
+        let loe = MultiPolygon(vec![rectangle((0.0, 0.0), (100.0, 20.0))]);
+        // leaves a strip running north
+        let exclusions = MultiPolygon(vec![rectangle((10.0, -1.0), (101.0, 21.0))]);
+        let excavation = Excavation::new(&loe, &exclusions, 0.0, CoverageBasis::Available);
+        let axis = excavation.principal_axis().unwrap();
+        assert!(axis.0.abs() < 1e-6 || (axis.0 - 180.0).abs() < 1e-6);
+        let strip = principal_axis(&excavation.available).unwrap();
+        assert!((strip.0 - 90.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn offsets_sweep_each_repeat_of_the_pattern() {
+        let grid = TrenchConfig::standard_grid(
+            2.0,
+            30.0,
+            array::Lattice::Square,
+            Distribution::Spacing(60.0),
+        )
+        .unwrap()
+        .with_offset_steps(3);
+        let (x_period, y_period) = grid.structure.get_translational_symmetry();
+        let offsets = grid.get_offsets();
+        assert_eq!(offsets.len(), 9);
+        assert!(offsets.iter().all(
+            |offset| (0.0..x_period).contains(&offset.x) && (0.0..y_period).contains(&offset.y)
+        ));
+        // lines only repeat across themselves
+        let lines = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0))
+            .unwrap()
+            .with_offset_steps(3);
+        assert_eq!(lines.get_offsets().len(), 3);
+        assert!(lines.get_offsets().iter().all(|offset| offset.y == 0.0));
+    }
+
+    #[test]
+    fn constructors_return_why_a_config_is_invalid() {
+        let coverage =
+            |percentage| Distribution::Coverage(Percentage::new_from_percentage(percentage));
+        let grid = |width, length, distribution| {
+            TrenchConfig::standard_grid(width, length, array::Lattice::Square, distribution)
+        };
+        assert!(matches!(
+            grid(f64::NAN, 30.0, coverage(5.0)),
+            Err(TrenchConfigError::NonPositiveWidth(_))
+        ));
+        assert!(matches!(
+            grid(2.0, -30.0, coverage(5.0)),
+            Err(TrenchConfigError::NonPositiveLength(_))
+        ));
+        for percentage in [0.0, 101.0, f64::NAN] {
+            assert!(matches!(
+                grid(2.0, 30.0, coverage(percentage)),
+                Err(TrenchConfigError::CoverageOutOfRange(_))
+            ));
+        }
+        // touching continuous trenches cover everything, so any coverage up to 100% is possible
+        assert!(TrenchConfig::continuous(2.0, coverage(100.0)).is_ok());
+        assert!(matches!(
+            grid(2.0, 30.0, coverage(100.0)),
+            Err(TrenchConfigError::CoverageUnattainable { .. })
+        ));
+        assert!(matches!(
+            grid(2.0, 30.0, Distribution::Count(0)),
+            Err(TrenchConfigError::NoTrenches)
+        ));
+        assert!(grid(2.0, 30.0, coverage(5.0)).is_ok());
+    }
+
+    #[test]
+    fn written_layouts_read_back_with_their_placement() {
+        let structure = TrenchConfig::standard_grid(
+            2.0,
+            30.0,
+            array::Lattice::Square,
+            Distribution::Spacing(60.0),
+        )
+        .unwrap()
+        .structure;
+        let placement = LayoutPlacement {
+            rotation: Degree(30.0),
+            spacing: 60.0,
+            offset: Offset::new(5.0, 10.0),
+        };
+        let trenches = MultiPolygon(vec![
+            rectangle((0.0, 0.0), (2.0, 30.0)),
+            rectangle((60.0, 0.0), (62.0, 15.0)),
+        ]);
+        let layout = TrenchLayout::new(trenches.clone(), &placement, structure, 4.5);
+        let path = std::env::temp_dir().join(format!("layout_{}.geojson", std::process::id()));
+        write_trench_layout(&layout, &path).unwrap();
+
+        let read = read_trench_layout(&path).unwrap();
+        assert_eq!(read.trenches, trenches);
+        let metadata = read.metadata.unwrap();
+        assert_eq!(metadata.rotation, 30.0);
+        assert_eq!((metadata.x_offset, metadata.y_offset), (5.0, 10.0));
+        assert_eq!(metadata.coverage, 4.5);
+
+        // each trench carries its own id, size and the placement of the layout
+        let GeoJson::FeatureCollection(collection) = read_geojson(&path).unwrap() else {
+            panic!("layouts are saved as a feature collection");
+        };
+        let properties = collection.features[1].properties.as_ref().unwrap();
+        assert_eq!(properties["trench_id"], 2);
+        assert_eq!(properties["spacing"], 60.0);
+        assert_eq!(properties["width"], 2.0);
+        assert_eq!(properties["clipped_length"], 15.0);
+        assert_eq!(properties["area"], 30.0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn exclusions_are_taken_out_of_the_available_area() {
+        let loe = MultiPolygon(vec![rectangle((0.0, 0.0), (100.0, 100.0))]);
+        // half of it outside the LOE
+        let exclusions = MultiPolygon(vec![rectangle((90.0, 40.0), (110.0, 60.0))]);
+        let available = Excavation::new(&loe, &exclusions, 0.0, CoverageBasis::Available);
+        assert!((available.available.unsigned_area() - 9800.0).abs() < 1e-6);
+        assert!((available.coverage_area - 9800.0).abs() < 1e-6);
+        // planning conditions may ask for coverage of the whole LOE
+        let whole = Excavation::new(&loe, &exclusions, 0.0, CoverageBasis::LimitOfExcavation);
+        assert!((whole.available.unsigned_area() - 9800.0).abs() < 1e-6);
+        assert!((whole.coverage_area - 10000.0).abs() < 1e-6);
+        let none = Excavation::new(&loe, &MultiPolygon(vec![]), 0.0, CoverageBasis::Available);
+        assert_eq!(none.available, loe);
+    }
+
+    #[test]
+    fn metadata_counts_the_trenches_cut_short() {
+        let grid = TrenchConfig::standard_grid(
+            2.0,
+            30.0,
+            array::Lattice::Square,
+            Distribution::Spacing(60.0),
+        )
+        .unwrap()
+        .structure;
+        let trenches = MultiPolygon(vec![
+            rectangle((0.0, 0.0), (2.0, 30.0)),
+            rectangle((60.0, 0.0), (62.0, 15.0)),
+        ]);
+        let placement = LayoutPlacement {
+            rotation: Degree(0.0),
+            spacing: 60.0,
+            offset: Offset::new(0.0, 0.0),
+        };
+        let metadata = LayoutMetadata::new(&trenches, &placement, grid, 3.0);
+        assert_eq!((metadata.trenches, metadata.clipped_trenches), (2, 1));
+        assert!((metadata.total_area - 90.0).abs() < 1e-9);
+        assert!((metadata.total_length - 45.0).abs() < 1e-9);
+        assert_eq!((metadata.trench_width, metadata.coverage), (2.0, 3.0));
+        // continuous trenches always run to the edge
+        let lines = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0))
+            .unwrap()
+            .structure;
+        let metadata = LayoutMetadata::new(&trenches, &placement, lines, 3.0);
+        assert_eq!(metadata.clipped_trenches, 2);
+    }
+
+    #[test]
+    fn lattice_positions_offset_every_other_row() {
+        use array::Lattice;
+        let distance = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| (x1 - x2).hypot(y1 - y2);
+        // every neighbour of a hexagonal trench is a spacing away
+        let hexagonal = Lattice::Hexagonal;
+        let centre = hexagonal.position(0, 0);
+        for (column, row) in [(1, 0), (-1, 0), (0, 1), (-1, 1), (0, -1), (-1, -1)] {
+            assert!((distance(centre, hexagonal.position(column, row)) - 1.0).abs() < 1e-12);
+        }
+        // the offset wraps to within a spacing and applies to rows below the origin too
+        let staggered = Lattice::Staggered { row_offset: 1.25 };
+        assert_eq!(staggered.position(2, 1), (2.25, 1.0));
+        assert_eq!(staggered.position(2, -1), (2.25, -1.0));
+        assert_eq!(staggered.position(2, 2), (2.0, 2.0));
+        let rectangular = Lattice::Rectangular { row_spacing: 2.5 };
+        assert_eq!(rectangular.position(1, 2), (1.0, 5.0));
+        assert_eq!(Lattice::Square.row_spacing(), 1.0);
+
+        // rows further apart cover less
+        let grid = |lattice| {
+            TrenchConfig::standard_grid(2.0, 30.0, lattice, Distribution::Spacing(60.0))
+                .unwrap()
+                .structure
+                .get_coverage_from_spacing(60.0)
+                .0
+        };
+        assert!((grid(Lattice::Square) / grid(rectangular) - 2.5).abs() < 1e-12);
+        assert!((grid(hexagonal) - grid(Lattice::Square) * 2.0 / 3.0_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn orientation_turns_layouts_from_the_principal_axis() {
+        // running east, where rotation 0 of a pattern runs north
+        let loe = MultiPolygon(vec![rectangle((0.0, 0.0), (100.0, 20.0))]);
+        let excavation =
+            Excavation::new(&loe, &MultiPolygon(vec![]), 0.0, CoverageBasis::Available);
+        let lines = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0)).unwrap();
+        let rotations = |orientation| {
+            lines
+                .with_orientation(orientation)
+                .get_rotations(&excavation)
+        };
+        assert_eq!(rotations(Orientation::Swept).len(), 180);
+        assert_eq!(rotations(Orientation::Aligned), vec![90]);
+        assert_eq!(rotations(Orientation::Perpendicular), vec![0]);
+        assert_eq!(rotations(Orientation::Angle(30.0)), vec![120]);
+        // auto sweeps every rotation and picks out the aligned one
+        let auto = lines.with_orientation(Orientation::Auto);
+        assert_eq!(auto.get_rotations(&excavation).len(), 180);
+        assert_eq!(auto.get_aligned_rotation(&excavation), Some(90));
+        // a square grid repeats every quarter turn
+        assert_eq!(
+            Orientation::Angle(-30.0).rotation(Degree(0.0), 90),
+            Some(60)
+        );
+    }
+}
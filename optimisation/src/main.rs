@@ -1,166 +1,699 @@
+mod centre_line;
+mod cli;
 mod intersects;
+mod optimise;
+mod radial;
+mod random;
 mod trench;
 
-use geo::Polygon;
+use anyhow::{Context, Result};
+use clap::Parser;
+use geo::MultiPolygon;
 use rayon::prelude::*;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+use cli::{Cli, Command, SettingOutFormatName, SiteArgs, StrategyName};
+use trenching_optimisation::catalogue::SiteCatalogue;
+use trenching_optimisation::cost::CostModel;
+use trenching_optimisation::detection::{
+    summarise_by_feature_type, write_detections, DetectionRecord, DetectionRule, DiscoveryRule,
+    FeatureDetection, LocationDetections,
+};
+use trenching_optimisation::experiment::{self, Experiment, RunResult};
+use trenching_optimisation::setting_out::{write_setting_out, SettingOutFormat};
+use trenching_optimisation::statistics::{
+    write_statistics, Bootstrap, LayoutSummary, StatisticsRecord, Summary,
+};
 use trenching_optimisation::{
-    read_all_test_location_data, read_single_test_location_data, Distribution, Percentage,
-    TrenchConfig, TrenchLayout,
+    read_all_test_location_data, read_centre_line, read_exclusions, read_features, read_focus,
+    read_limit_of_excavation, read_single_test_location_data, read_trench_layout,
+    write_trench_layout, ArchaeologicalFeature, Distribution, Excavation, LayoutMetadata, Offset,
+    Orientation, Percentage, TestLocation, TrenchConfig, TrenchLayout,
 };
+
 // fn main() {
 //     let new_rectangle = Rectangle::new(2.0, 30.0);
 //     test_get_minimum_spacing(new_rectangle, Degree(20.0), Degree(88.0));
 // }
-fn main() {
-    let continous_spacing = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0));
-    let parallel_array_spacing =
-        TrenchConfig::parallel_array(2.0, 30.0, Distribution::Spacing(20.0));
-    let standard_grid_spacing = TrenchConfig::standard_grid(2.0, 30.0, Distribution::Spacing(20.0));
-    // let grid_with_wide_trenches_spacing =
-    //     TrenchConfig::standard_grid(4.0, 30.0, Distribution::Spacing(40.0));
-    // let grid_wtth_short_trenches_spacing =
-    //     TrenchConfig::standard_grid(2.0, 20.0, Distribution::Spacing(30.0));
-    // let test_pits_spacing = TrenchConfig::test_pits(1.0, Distribution::Spacing(20.0));
-
-    let continous_coverage = TrenchConfig::continuous(
-        2.0,
-        Distribution::Coverage(Percentage::new_from_percentage(5.0)),
-    );
-    let parallel_array_coverage = TrenchConfig::parallel_array(
-        2.0,
-        30.0,
-        Distribution::Coverage(Percentage::new_from_percentage(5.0)),
-    );
-    let standard_grid_coverage = TrenchConfig::standard_grid(
-        2.0,
-        30.0,
-        Distribution::Coverage(Percentage::new_from_percentage(5.0)),
-    );
-    // let grid_with_wide_trenches_coverage =
-    //     TrenchConfig::standard_grid(4.0, 30.0, Distribution::Coverage(Percentage::new_from_percentage(5.0)));
-    // let grid_wtth_short_trenches_coverage =
-    //     TrenchConfig::standard_grid(2.0, 20.0, Distribution::Coverage(Percentage::new_from_percentage(5.0)));
-    // let test_pits_coverage = TrenchConfig::test_pits(1.0, Distribution::Coverage(Percentage::new_from_percentage(5.0)));
-
-    let selected_layer = Some("Middle Bronze Age");
-    // let selected_layer: Option<&str> = None;
-
-    for config in [
-        continous_spacing,
-        parallel_array_spacing,
-        standard_grid_spacing,
-        // grid_with_wide_trenches_spacing,
-        // grid_wtth_short_trenches_spacing,
-        // test_pits_spacing,
-        continous_coverage,
-        parallel_array_coverage,
-        standard_grid_coverage,
-        // grid_with_wide_trenches_coverage,
-        // grid_wtth_short_trenches_coverage,
-        // test_pits_coverage,
-    ]
-    .iter()
-    {
-        // run_on_single_loe(
-        //     &config,
-        //     "Stansted".to_string(),
-        //     "0".to_string(),
-        //     selected_layer,
-        // );
-        run_on_all_loes(&config, selected_layer);
-    }
-}
-
-fn count_features_hit_or_missed(
-    features: &Vec<Polygon<f64>>,
-    trenches: &TrenchLayout,
-) -> (i32, i32) {
-    let mut features_found = 0;
-    let mut features_missed = 0;
-    for feature in features {
-        if intersects::test(feature, trenches) {
-            features_found += 1;
-        } else {
-            features_missed += 1;
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Generate {
+            loe,
+            output,
+            trench,
+            lattice,
+            distribution,
+            exclusions,
+            centre_line,
+            focus,
+            coverage_of,
+            boundary_buffer,
+            clipping,
+            solver,
+            cost,
+            placement,
+            orientation,
+            rotation,
+            x_offset,
+            y_offset,
+        } => {
+            let config = TrenchConfig::of_type(
+                trench.trench_type,
+                trench.width,
+                trench.length,
+                lattice.lattice(),
+                distribution.distribution(),
+            )?
+            .with_coverage_basis(coverage_of.basis())
+            .with_boundary_buffer(boundary_buffer)
+            .with_clipping(clipping.policy())
+            .with_solver(solver.solver())
+            .with_cost_model(cost.cost_model())
+            .with_placement(placement.placement())
+            .with_orientation(orientation.orientation());
+            let limit_of_excavation = read_limit_of_excavation(&loe)?;
+            let exclusions = match exclusions {
+                Some(exclusions) => read_exclusions(&exclusions)?,
+                None => MultiPolygon(vec![]),
+            };
+            let excavation = Excavation::new(
+                &limit_of_excavation,
+                &exclusions,
+                config.boundary_buffer,
+                config.coverage_basis,
+            )
+            .with_centre_line(
+                centre_line
+                    .map(|path| read_centre_line(&path))
+                    .transpose()?,
+            )
+            .with_focus(focus.map(|path| read_focus(&path)).transpose()?);
+            if config.orientation != Orientation::Swept {
+                if let Some(axis) = excavation.principal_axis() {
+                    println!("Principal axis of the LOE at {:.1}° from east", axis.0);
+                }
+            }
+            let rotation = config.get_fixed_rotation(&excavation).unwrap_or(rotation);
+            let trenches = trench::create_single_layout(
+                &config,
+                &excavation,
+                rotation,
+                Offset::new(x_offset, y_offset),
+            )
+            .context("No trenches created for LOE")?;
+            write_trench_layout(&trenches, &output)?;
+            println!("Saved layout to {:?}", output);
+            if let Some(metadata) = trenches.metadata {
+                println!("  {}", metadata);
+            }
+            println!("  {}", config.cost.estimate_layout(&trenches));
+        }
+        Command::Evaluate {
+            layout,
+            features,
+            layer,
+            detection,
+            discovery,
+            detections,
+        } => run_on_layout(
+            &layout,
+            &features,
+            layer.as_deref(),
+            &detection.rule(),
+            &discovery.rule(),
+            detections.as_deref(),
+        )?,
+        Command::SettingOut {
+            layout,
+            output,
+            format,
+        } => {
+            let format = match format {
+                SettingOutFormatName::Trenches => SettingOutFormat::Trenches,
+                SettingOutFormatName::SurveyPoints => SettingOutFormat::SurveyPoints,
+            };
+            let trenches = read_trench_layout(&layout)?;
+            write_setting_out(&trenches, format, &output)?;
+            println!(
+                "Saved setting out for {} trenches to {:?}",
+                trenches.trenches.0.len(),
+                output
+            );
+        }
+        Command::Benchmark {
+            sites,
+            loe,
+            trench_types,
+            width,
+            length,
+            lattice,
+            spacings,
+            coverages,
+            budgets,
+            counts,
+            coverage_of,
+            boundary_buffer,
+            clipping,
+            solver,
+            cost,
+            placement,
+            orientation,
+            offset_steps,
+            detection,
+            discovery,
+            detections,
+            statistics,
+        } => {
+            let catalogue = load_catalogue(&sites)?;
+            let rule = detection.rule();
+            let distributions = spacings
+                .into_iter()
+                .map(Distribution::Spacing)
+                .chain(coverages.into_iter().map(|coverage| {
+                    Distribution::Coverage(Percentage::new_from_percentage(coverage))
+                }))
+                .chain(budgets.into_iter().map(Distribution::Cost))
+                .chain(counts.into_iter().map(Distribution::Count));
+            let distributions: Vec<Distribution> = distributions.collect();
+            let bootstrap = Bootstrap::default();
+            let mut detection_records = Vec::new();
+            let mut statistics_records = Vec::new();
+            for trench_type in trench_types.iter() {
+                for distribution in distributions.iter() {
+                    let config = TrenchConfig::of_type(
+                        *trench_type,
+                        width,
+                        length,
+                        lattice.lattice(),
+                        *distribution,
+                    )?
+                    .with_offset_steps(offset_steps)
+                    .with_coverage_basis(coverage_of.basis())
+                    .with_boundary_buffer(boundary_buffer)
+                    .with_clipping(clipping.policy())
+                    .with_solver(solver.solver())
+                    .with_cost_model(cost.cost_model())
+                    .with_placement(placement.placement())
+                    .with_orientation(orientation.orientation());
+                    println!(
+                        "\nTesting {} trenches with {:?}, {} placement",
+                        trench_type, distribution, config.placement
+                    );
+                    let location_detections: Vec<LocationDetections> = match loe {
+                        Some(ref loe_i) => catalogue
+                            .sites
+                            .iter()
+                            .filter_map(|site| {
+                                run_on_single_loe(
+                                    &catalogue,
+                                    &config,
+                                    site.name.clone(),
+                                    loe_i.clone(),
+                                    sites.layer.as_deref(),
+                                    &rule,
+                                )
+                            })
+                            .collect(),
+                        None => {
+                            run_on_all_loes(&catalogue, &config, sites.layer.as_deref(), &rule)?
+                        }
+                    };
+                    print_site_discovery(&location_detections, &discovery.rule());
+                    let run = format!("{} {:?}", trench_type, distribution);
+                    let (summary, records) =
+                        summarise_detection_rates(&run, &location_detections, &bootstrap);
+                    if let Some(summary) = summary {
+                        print_detection_rates(&summary, &bootstrap);
+                        if config.orientation == Orientation::Auto {
+                            print_aligned_detection_rates(
+                                &location_detections,
+                                &summary,
+                                &bootstrap,
+                            );
+                        }
+                    }
+                    statistics_records.extend(records);
+                    if detections.is_some() {
+                        for location in location_detections.iter() {
+                            detection_records.extend(location.records(&run));
+                        }
+                    }
+                }
+            }
+            if let Some(detections_path) = detections {
+                write_detections(&detection_records, &detections_path)?;
+                println!("\nSaved detections to {:?}", detections_path);
+            }
+            if let Some(statistics_path) = statistics {
+                write_statistics(&statistics_records, &statistics_path)?;
+                println!("\nSaved statistics to {:?}", statistics_path);
+            }
         }
+        Command::Experiment { experiment, output } => run_experiment(&experiment, output)?,
+        Command::Optimise {
+            sites,
+            loe,
+            trench,
+            lattice,
+            coverage,
+            coverage_of,
+            boundary_buffer,
+            clipping,
+            solver,
+            strategies,
+            steps,
+            restarts,
+            iterations,
+            initial_temperature,
+            cooling_rate,
+            seed,
+            prior_count,
+            prior_size,
+            detection,
+            output,
+        } => {
+            let catalogue = load_catalogue(&sites)?;
+            let config = TrenchConfig::of_type(
+                trench.trench_type,
+                trench.width,
+                trench.length,
+                lattice.lattice(),
+                Distribution::Coverage(Percentage::new_from_percentage(coverage)),
+            )?
+            .with_coverage_basis(coverage_of.basis())
+            .with_boundary_buffer(boundary_buffer)
+            .with_clipping(clipping.policy())
+            .with_solver(solver.solver());
+            let strategy_names = if strategies.is_empty() {
+                vec![
+                    StrategyName::GridSearch,
+                    StrategyName::RandomRestart,
+                    StrategyName::SimulatedAnnealing,
+                ]
+            } else {
+                strategies
+            };
+            let strategies: Vec<optimise::Strategy> = strategy_names
+                .iter()
+                .map(|strategy_name| match strategy_name {
+                    StrategyName::GridSearch => optimise::Strategy::GridSearch { steps },
+                    StrategyName::RandomRestart => optimise::Strategy::RandomRestart {
+                        restarts,
+                        iterations: iterations / restarts.max(1),
+                        seed,
+                    },
+                    StrategyName::SimulatedAnnealing => optimise::Strategy::SimulatedAnnealing {
+                        iterations,
+                        initial_temperature,
+                        cooling_rate,
+                        seed,
+                    },
+                })
+                .collect();
+            let prior = prior_count.map(|count| optimise::Prior {
+                feature_size: prior_size,
+                count,
+                seed,
+            });
+            for site in catalogue.sites.iter() {
+                // one layout is written per site
+                let output = output.as_deref().map(|output| {
+                    if catalogue.sites.len() > 1 {
+                        site_output_path(output, &site.name)
+                    } else {
+                        output.to_path_buf()
+                    }
+                });
+                run_optimisers_on_single_loe(
+                    &catalogue,
+                    &config,
+                    site.name.clone(),
+                    loe.clone(),
+                    sites.layer.as_deref(),
+                    &strategies,
+                    prior,
+                    &detection.rule(),
+                    output.as_deref(),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_catalogue(sites: &SiteArgs) -> Result<SiteCatalogue> {
+    let catalogue = SiteCatalogue::load(&sites.data_root)?;
+    if sites.site_names.is_empty() {
+        Ok(catalogue)
+    } else {
+        Ok(catalogue.select_sites(&sites.site_names))
+    }
+}
+
+fn detect_features(
+    features: &[ArchaeologicalFeature],
+    trenches: &TrenchLayout,
+    rule: &DetectionRule,
+) -> Vec<FeatureDetection> {
+    features
+        .iter()
+        .map(|feature| intersects::detect(feature, trenches, rule))
+        .collect()
+}
+
+fn detect_features_in_layouts(
+    test_location: &TestLocation,
+    trenches: Vec<TrenchLayout>,
+    rule: &DetectionRule,
+    aligned_rotation: Option<f64>,
+) -> LocationDetections {
+    let layout_metadata = trenches.iter().map(|trench| trench.metadata).collect();
+    let layouts: Vec<Vec<FeatureDetection>> = trenches
+        .into_par_iter()
+        .map(|trench| detect_features(&test_location.features, &trench, rule))
+        .collect();
+    LocationDetections {
+        site_name: test_location.site_name.clone(),
+        loe_i: test_location.loe_i.clone(),
+        period: test_location.period.clone(),
+        geology: test_location.geology.clone(),
+        features: test_location.features.clone(),
+        layouts,
+        layout_metadata,
+        aligned_rotation,
+    }
+}
+
+// the rotation along the principal axis of the LOE, which auto orientation compares with the rest
+fn aligned_rotation(config: &TrenchConfig, excavation: &Excavation) -> Option<f64> {
+    match config.orientation {
+        Orientation::Auto => config.get_aligned_rotation(excavation).map(f64::from),
+        _ => None,
     }
-    (features_found, features_missed)
 }
 
 fn run_on_single_loe(
+    catalogue: &SiteCatalogue,
     config: &TrenchConfig,
     site_name: String,
     loe_i: String,
     selected_layer: Option<&str>,
-) {
+    rule: &DetectionRule,
+) -> Option<LocationDetections> {
     // println!("\nRunning {:?} on single LOE", config.layout);
-    let test_location = read_single_test_location_data(site_name, loe_i, selected_layer);
+    let test_location = read_single_test_location_data(catalogue, site_name, loe_i, selected_layer);
     match test_location {
         Ok(test_location) => {
             let now = Instant::now();
-            let trenches = trench::create_layouts(config, test_location.limit_of_excavation);
+            let excavation = test_location.excavation(config);
+            let trenches = trench::create_layouts(config, &excavation);
             println!("Creating trenches took: {:?}", now.elapsed());
             match trenches {
                 Some(trenches) => {
                     let now = Instant::now();
-                    let _: Vec<(i32, i32)> = trenches
-                        .into_par_iter()
-                        .map(|trench| {
-                            let (features_found, features_missed) =
-                                count_features_hit_or_missed(&test_location.features, &trench);
-                            (features_found, features_missed)
-                        })
-                        .collect();
+                    let detections = detect_features_in_layouts(
+                        &test_location,
+                        trenches,
+                        rule,
+                        aligned_rotation(config, &excavation),
+                    );
                     println!("Calculating features hit took: {:?}", now.elapsed());
+                    let (total_found, total_missed) = detections.count_found_and_missed();
+                    println!(
+                        "Total features found: {}, total features missed: {}, percentage found: {:.2}%",
+                        total_found,
+                        total_missed,
+                        total_found as f64 / (total_found + total_missed) as f64 * 100.0
+                    );
+                    println!("Total trench patterns tested: {}", detections.layouts.len());
+                    if let Some(summary) = LayoutSummary::new(&detections.metadata()) {
+                        print_layout_summary(&summary, &config.cost);
+                    }
+                    Some(detections)
                 }
                 None => {
                     println!("No trenches created for LOE");
-                    return;
+                    None
                 }
             }
         }
         Err(e) => {
             println!("{:?}", e);
+            None
         }
     }
 }
 
-fn run_on_all_loes(config: &TrenchConfig, selected_layer: Option<&str>) {
+fn run_on_layout(
+    layout_path: &Path,
+    features_path: &Path,
+    selected_layer: Option<&str>,
+    rule: &DetectionRule,
+    discovery: &DiscoveryRule,
+    detections_path: Option<&Path>,
+) -> Result<()> {
+    let trenches = read_trench_layout(layout_path)?;
+    if let Some(metadata) = trenches.metadata {
+        println!("Layout: {}", metadata);
+    }
+    let features = read_features(features_path, selected_layer)?;
+    let detections = LocationDetections {
+        site_name: String::new(),
+        loe_i: String::new(),
+        period: None,
+        geology: None,
+        layouts: vec![detect_features(&features, &trenches, rule)],
+        layout_metadata: vec![trenches.metadata],
+        features,
+        aligned_rotation: None,
+    };
+    let (features_found, features_missed) = detections.count_found_and_missed();
+    println!(
+        "Features found: {}, features missed: {}, percentage found: {:.2}%",
+        features_found,
+        features_missed,
+        features_found as f64 / (features_found + features_missed) as f64 * 100.0
+    );
+    println!(
+        "Site discovered ({}): {}",
+        discovery,
+        detections.count_discovered(discovery) == 1
+    );
+    let records = detections.records(&layout_path.to_string_lossy());
+    print_feature_type_summary(&records);
+    if let Some(detections_path) = detections_path {
+        write_detections(&records, detections_path)?;
+        println!("Saved detections to {:?}", detections_path);
+    }
+    Ok(())
+}
+
+fn print_feature_type_summary(records: &[DetectionRecord]) {
+    for summary in summarise_by_feature_type(records) {
+        println!(
+            "  {} {}: {} of {} found ({:.2}%), mean area {:.2}m²",
+            summary.layer.as_deref().unwrap_or("-"),
+            summary.feature_type.as_deref().unwrap_or("-"),
+            summary.found,
+            summary.tests,
+            summary.percentage_found(),
+            summary.mean_feature_area
+        );
+    }
+}
+
+fn count_discovered(detections: &[LocationDetections], rule: &DiscoveryRule) -> i32 {
+    detections
+        .iter()
+        .map(|location| location.count_discovered(rule))
+        .sum()
+}
+
+// spread of the percentage found by each layout at each LOE, then over every LOE
+fn summarise_detection_rates(
+    run: &str,
+    detections: &[LocationDetections],
+    bootstrap: &Bootstrap,
+) -> (Option<Summary>, Vec<StatisticsRecord>) {
+    let mut records: Vec<StatisticsRecord> = detections
+        .iter()
+        .filter_map(|location| {
+            Summary::new(&location.detection_rates(), bootstrap).map(|summary| {
+                StatisticsRecord::new(run, &location.site_name, &location.loe_i, &summary)
+                    .with_site(location.period.clone(), location.geology.clone())
+                    .with_layout_summary(LayoutSummary::new(&location.metadata()))
+            })
+        })
+        .collect();
+    let rates: Vec<f64> = detections
+        .iter()
+        .flat_map(|location| location.detection_rates())
+        .collect();
+    let summary = Summary::new(&rates, bootstrap);
+    if let Some(ref summary) = summary {
+        records.push(
+            StatisticsRecord::new(run, "all", "all", summary)
+                .with_layout_summary(summarise_layouts(detections)),
+        );
+    }
+    (summary, records)
+}
+
+fn summarise_layouts(detections: &[LocationDetections]) -> Option<LayoutSummary> {
+    let metadata: Vec<LayoutMetadata> = detections
+        .iter()
+        .flat_map(|location| location.metadata())
+        .collect();
+    LayoutSummary::new(&metadata)
+}
+
+fn print_layout_summary(summary: &LayoutSummary, cost: &CostModel) {
+    println!(
+        "Layouts: mean coverage {:.2}%, {:.1} trenches ({:.1} clipped), {:.1}m of trench over {:.1}m²",
+        summary.mean_coverage,
+        summary.mean_trenches,
+        summary.mean_clipped_trenches,
+        summary.mean_trench_length,
+        summary.mean_trench_area
+    );
+    println!(
+        "  mean layout: {}",
+        cost.estimate(summary.mean_trench_area, summary.mean_trenches)
+    );
+}
+
+fn print_detection_rates(summary: &Summary, bootstrap: &Bootstrap) {
+    println!(
+        "Percentage found by each layout: mean {:.2}%, median {:.2}%, sd {:.2}, min {:.2}%, max {:.2}%",
+        summary.mean, summary.median, summary.standard_deviation, summary.min, summary.max
+    );
+    println!(
+        "  5th to 95th percentile: {:.2}% to {:.2}%, {}% confidence interval of the mean: {:.2}% to {:.2}%",
+        summary.percentile_5,
+        summary.percentile_95,
+        bootstrap.confidence * 100.0,
+        summary.confidence_interval.0,
+        summary.confidence_interval.1
+    );
+}
+
+// percentage found by the layouts along the principal axis of each LOE
+fn summarise_aligned_detection_rates(
+    detections: &[LocationDetections],
+    bootstrap: &Bootstrap,
+) -> Option<Summary> {
+    let rates: Vec<f64> = detections
+        .iter()
+        .flat_map(|location| location.aligned_detection_rates())
+        .collect();
+    Summary::new(&rates, bootstrap)
+}
+
+// how the site-aligned layouts did against the expectation over every rotation
+fn print_aligned_detection_rates(
+    detections: &[LocationDetections],
+    summary: &Summary,
+    bootstrap: &Bootstrap,
+) {
+    match summarise_aligned_detection_rates(detections, bootstrap) {
+        Some(aligned) => println!(
+            "  aligned with the principal axis: mean {:.2}% ({:.2}% to {:.2}%), {:+.2} points on the mean over every rotation",
+            aligned.mean,
+            aligned.confidence_interval.0,
+            aligned.confidence_interval.1,
+            aligned.mean - summary.mean
+        ),
+        None => println!("  no layouts aligned with the principal axis"),
+    }
+}
+
+// chance a layout at a location would have found the site, rather than a share of its features
+fn print_site_discovery(detections: &[LocationDetections], rule: &DiscoveryRule) {
+    let (_, _, total_trenches) = count_totals(detections);
+    let discovered = count_discovered(detections, rule);
+    println!(
+        "Site discovered ({}) by {} of {} trench patterns, probability of discovery: {:.2}%",
+        rule,
+        discovered,
+        total_trenches,
+        discovered as f64 / total_trenches as f64 * 100.0
+    );
+}
+
+fn run_on_all_loes(
+    catalogue: &SiteCatalogue,
+    config: &TrenchConfig,
+    selected_layer: Option<&str>,
+    rule: &DetectionRule,
+) -> Result<Vec<LocationDetections>> {
     // println!("\nRunning {:?} on all LOEs", config.layout);
     println!("\nRunning on all LOEs");
-    let test_locations = read_all_test_location_data(selected_layer).unwrap();
+    let test_locations = read_all_test_location_data(catalogue, selected_layer)
+        .context("Could not read the test locations")?;
 
     let now = Instant::now();
 
-    let mut total_found = 0;
-    let mut total_missed = 0;
-    let mut total_trenches = 0;
+    let detections = run_on_test_locations(config, &test_locations, rule);
+    let (total_found, total_missed, total_trenches) = count_totals(&detections);
+
+    let percentage_found = total_found as f64 / (total_found + total_missed) as f64 * 100.0;
+
+    // println!(
+    //     "Testing {}m wide {:?} trenches",
+    //     config.width, config.layout
+    // );
+    println!(
+        "Total features found: {}, total features missed: {}, percentage found: {:.2}%",
+        total_found, total_missed, percentage_found
+    );
+    println!("Total trench patterns tested: {}", total_trenches);
+    if let Some(summary) = summarise_layouts(&detections) {
+        print_layout_summary(&summary, &config.cost);
+    }
+    println!("Testing took: {:?}", now.elapsed());
+    Ok(detections)
+}
+
+// features found, features missed and trench patterns tested
+fn count_totals(detections: &[LocationDetections]) -> (i32, i32, i32) {
+    detections.iter().fold(
+        (0, 0, 0),
+        |(total_found, total_missed, total_trenches), location| {
+            let (found, missed) = location.count_found_and_missed();
+            (
+                total_found + found,
+                total_missed + missed,
+                total_trenches + location.layouts.len() as i32,
+            )
+        },
+    )
+}
+
+fn run_on_test_locations(
+    config: &TrenchConfig,
+    test_locations: &[TestLocation],
+    rule: &DetectionRule,
+) -> Vec<LocationDetections> {
+    let mut detections = Vec::new();
 
     let mut total_trench_creation_time = 0;
     let mut total_testing_time = 0;
 
     for test_location in test_locations {
         let trenches_time = Instant::now();
-        let trenches = trench::create_layouts(config, test_location.limit_of_excavation);
+        let excavation = test_location.excavation(config);
+        let trenches = trench::create_layouts(config, &excavation);
         total_trench_creation_time += trenches_time.elapsed().as_millis();
         let testing_time = Instant::now();
         match trenches {
             Some(trenches) => {
-                let found_or_missed: Vec<(i32, i32)> = trenches
-                    .into_par_iter()
-                    .map(|trench| {
-                        let (features_found, features_missed) =
-                            count_features_hit_or_missed(&test_location.features, &trench);
-                        (features_found, features_missed)
-                    })
-                    .collect();
-                for (found, missed) in found_or_missed {
-                    total_found += found;
-                    total_missed += missed;
-                    total_trenches += 1;
-                }
+                detections.push(detect_features_in_layouts(
+                    test_location,
+                    trenches,
+                    rule,
+                    aligned_rotation(config, &excavation),
+                ));
             }
             None => {
                 println!("No trenches created for LOE");
@@ -171,17 +704,172 @@ fn run_on_all_loes(config: &TrenchConfig, selected_layer: Option<&str>) {
     }
     println!("Creating trenches took: {:?}s", total_trench_creation_time as f64 / 1000.0);
     println!("Testing trenches took: {:?}s", total_testing_time as f64 / 1000.0);
+    detections
+}
 
-    let percentage_found = total_found as f64 / (total_found + total_missed) as f64 * 100.0;
+fn run_experiment(experiment_path: &Path, output: Option<PathBuf>) -> Result<()> {
+    let experiment = Experiment::load(experiment_path)?;
+    let data_root = experiment
+        .data_root
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(cli::DATA_ROOT));
+    let catalogue = SiteCatalogue::load(data_root)?;
+    let runs = experiment.runs(&catalogue)?;
+    let output = output
+        .or_else(|| experiment.output.clone())
+        .expect("Experiment::load sets the output");
+    println!("Running {} combinations of {}", runs.len(), experiment.name);
 
-    // println!(
-    //     "Testing {}m wide {:?} trenches",
-    //     config.width, config.layout
-    // );
-    println!(
-        "Total features found: {}, total features missed: {}, percentage found: {:.2}%",
-        total_found, total_missed, percentage_found
-    );
-    println!("Total trench patterns tested: {}", total_trenches);
-    println!("Testing took: {:?}", now.elapsed());
+    let bootstrap = Bootstrap::default();
+    let mut results = Vec::new();
+    let mut detection_records = Vec::new();
+    let mut statistics_records = Vec::new();
+    for (i, run) in runs.iter().enumerate() {
+        println!(
+            "\n{}/{}: {} {} trenches with {:?} on {} for {:?}, found when {}",
+            i + 1,
+            runs.len(),
+            run.trench.width,
+            run.trench.trench_type,
+            run.distribution,
+            run.site_group.name,
+            run.layer,
+            experiment.detection
+        );
+        let test_locations = read_all_test_location_data(
+            &catalogue.clone().select_sites(&run.site_group.sites),
+            run.layer.as_deref(),
+        )?;
+        let detections = run_on_test_locations(&run.config, &test_locations, &experiment.detection);
+        let (found, missed, patterns) = count_totals(&detections);
+        let discovered = count_discovered(&detections, &experiment.discovery);
+        let result = RunResult::new(
+            &experiment,
+            i + 1,
+            run,
+            test_locations.len(),
+            patterns,
+            found,
+            missed,
+            discovered,
+        );
+        let (summary, records) =
+            summarise_detection_rates(&(i + 1).to_string(), &detections, &bootstrap);
+        let aligned = match experiment.orientation {
+            Orientation::Auto => summarise_aligned_detection_rates(&detections, &bootstrap),
+            _ => None,
+        };
+        let result = result
+            .with_detection_rates(summary)
+            .with_aligned_detection_rates(aligned)
+            .with_layout_summary(summarise_layouts(&detections), &experiment.cost);
+        statistics_records.extend(records);
+        println!(
+            "Percentage found: {:.2}%, probability of discovery: {:.2}%",
+            result.percentage_found, result.site_discovery_probability
+        );
+        if let Some(aligned_rate_mean) = result.aligned_rate_mean {
+            println!(
+                "Mean found by layouts aligned with the principal axis: {:.2}%",
+                aligned_rate_mean
+            );
+        }
+        results.push(result);
+        if experiment.detections.is_some() {
+            for location in detections.iter() {
+                detection_records.extend(location.records(&(i + 1).to_string()));
+            }
+        }
+    }
+
+    experiment::write_results(&results, &output)?;
+    println!("\nSaved {} results to {:?}", results.len(), output);
+    if let Some(ref detections_path) = experiment.detections {
+        write_detections(&detection_records, detections_path)?;
+        println!("Saved detections to {:?}", detections_path);
+    }
+    if let Some(ref statistics_path) = experiment.statistics {
+        write_statistics(&statistics_records, statistics_path)?;
+        println!("Saved statistics to {:?}", statistics_path);
+    }
+    Ok(())
+}
+
+// the output with the site's name added to the file name, such as best_Stansted.geojson
+fn site_output_path(output: &Path, site_name: &str) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let file_name = match output.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, site_name, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, site_name),
+    };
+    output.with_file_name(file_name)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_optimisers_on_single_loe(
+    catalogue: &SiteCatalogue,
+    config: &TrenchConfig,
+    site_name: String,
+    loe_i: String,
+    selected_layer: Option<&str>,
+    strategies: &[optimise::Strategy],
+    prior: Option<optimise::Prior>,
+    rule: &DetectionRule,
+    output: Option<&Path>,
+) -> Result<()> {
+    println!("\nOptimising layout for {} location {}", site_name, loe_i);
+    let test_location = read_single_test_location_data(catalogue, site_name, loe_i, selected_layer)
+        .and_then(|test_location| match prior {
+            Some(prior) => optimise::test_location_from_prior(test_location, &prior),
+            None => Ok(test_location),
+        });
+    let test_location: TestLocation = match test_location {
+        Ok(test_location) => test_location,
+        Err(e) => {
+            println!("{:?}", e);
+            return Ok(());
+        }
+    };
+    let search_space = optimise::SearchSpace::new(config);
+
+    let mut best: Option<optimise::Solution> = None;
+    for strategy in strategies.iter() {
+        let now = Instant::now();
+        match optimise::optimise(config, &test_location, &search_space, strategy, rule) {
+            Ok(solution) => {
+                let candidate = solution.candidate;
+                println!(
+                    "{:?}: {:.2}% found with {:.2}% coverage and {} trenches",
+                    strategy,
+                    solution.score * 100.0,
+                    solution.coverage.0,
+                    solution.layout.trenches.0.len()
+                );
+                println!(
+                    "  spacing: {:.2}m, rotation: {:.1}, offset: ({:.2}, {:.2}), length: {:.1}m, angles: ({:.1}, {:.1})",
+                    candidate.spacing,
+                    candidate.rotation.0,
+                    candidate.offset.x,
+                    candidate.offset.y,
+                    candidate.length,
+                    candidate.base_angle.0,
+                    candidate.alternate_angle.0
+                );
+                if best.as_ref().is_none_or(|best| solution.score > best.score) {
+                    best = Some(solution);
+                }
+            }
+            Err(e) => println!("{:?}: {:?}", strategy, e),
+        }
+        println!("Optimising took: {:?}", now.elapsed());
+    }
+
+    if let (Some(output), Some(best)) = (output, best) {
+        write_trench_layout(&best.layout, output)?;
+        println!("Saved best layout to {:?}", output);
+    }
+    Ok(())
 }
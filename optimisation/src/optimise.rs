@@ -0,0 +1,644 @@
+use anyhow::{anyhow, Result};
+use geo::{coord, BoundingRect, Contains, LineString, MultiPolygon, Point, Polygon};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use trenching_optimisation::array::Configuration;
+use trenching_optimisation::detection::DetectionRule;
+use trenching_optimisation::{
+    ArchaeologicalFeature, Degree, Distribution, Excavation, FeatureGeometry, Offset, Percentage,
+    Rectangle, Structure, TestLocation, TrenchConfig, TrenchLayout,
+};
+
+use crate::{intersects, trench};
+
+// order of the dimensions in a search point
+const SPACING: usize = 0;
+const ROTATION: usize = 1;
+const X_OFFSET: usize = 2;
+const Y_OFFSET: usize = 3;
+const LENGTH: usize = 4;
+const BASE_ANGLE: usize = 5;
+const ALTERNATE_ANGLE: usize = 6;
+const DIMENSIONS: usize = 7;
+
+// angles and offsets wrap around rather than being clamped
+const PERIODIC: [bool; DIMENSIONS] = [false, true, true, true, false, true, true];
+
+// fraction of a dimension's range moved in a single step of a local search
+const STEP_SIZE: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Strategy {
+    GridSearch {
+        steps: usize,
+    },
+    RandomRestart {
+        restarts: usize,
+        iterations: usize,
+        seed: u64,
+    },
+    SimulatedAnnealing {
+        iterations: usize,
+        initial_temperature: f64,
+        cooling_rate: f64,
+        seed: u64,
+    },
+}
+
+// square features scattered uniformly over an LOE when the real features are unknown
+#[derive(Debug, Clone, Copy)]
+pub struct Prior {
+    pub feature_size: f64,
+    pub count: usize,
+    pub seed: u64,
+}
+
+// (min, max) of each searched parameter, a parameter is fixed when min == max
+#[derive(Debug, Clone, Copy)]
+pub struct SearchSpace {
+    pub spacing_factor: (f64, f64), // multiple of the spacing estimated from the coverage budget
+    pub rotation: (f64, f64),
+    pub x_offset: (f64, f64), // fraction of spacing
+    pub y_offset: (f64, f64), // fraction of spacing
+    pub length: (f64, f64),
+    pub base_angle: (f64, f64),
+    pub alternate_angle: (f64, f64),
+}
+
+impl SearchSpace {
+    pub fn new(config: &TrenchConfig) -> Self {
+        let (x_period, y_period) = config.structure.get_translational_symmetry();
+        let rotation = (0.0, config.structure.get_rotational_symmetry() as f64);
+        let x_offset = (0.0, x_period);
+        let y_offset = (0.0, y_period);
+        match config.structure {
+            Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => SearchSpace {
+                spacing_factor: (0.8, 1.5),
+                rotation,
+                x_offset,
+                y_offset,
+                length: (0.0, 0.0),
+                base_angle: (0.0, 0.0),
+                alternate_angle: (0.0, 0.0),
+            },
+            Structure::Array(rectangle, _) => SearchSpace {
+                spacing_factor: (0.8, 1.5),
+                rotation,
+                x_offset,
+                y_offset,
+                length: (
+                    rectangle.width.max(rectangle.length * 0.5),
+                    rectangle.length * 1.5,
+                ),
+                base_angle: (0.0, 180.0),
+                alternate_angle: (0.0, 180.0),
+            },
+        }
+    }
+
+    fn bounds(&self) -> [(f64, f64); DIMENSIONS] {
+        [
+            self.spacing_factor,
+            self.rotation,
+            self.x_offset,
+            self.y_offset,
+            self.length,
+            self.base_angle,
+            self.alternate_angle,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub spacing: f64,
+    pub rotation: Degree,
+    pub offset: Offset,
+    pub length: f64,
+    pub base_angle: Degree,
+    pub alternate_angle: Degree,
+}
+
+#[derive(Debug)]
+pub struct Solution {
+    pub candidate: Candidate,
+    pub layout: TrenchLayout,
+    pub score: f64, // fraction of features hit
+    pub coverage: Percentage,
+}
+
+impl Solution {
+    fn is_better_than(&self, other: &Solution) -> bool {
+        // prefer the cheaper layout when detection is equal
+        self.score > other.score
+            || (self.score == other.score && self.coverage.0 < other.coverage.0)
+    }
+}
+
+struct Problem<'a> {
+    config: &'a TrenchConfig,
+    excavation: Excavation,
+    features: &'a Vec<ArchaeologicalFeature>,
+    budget: Percentage,
+    bounds: [(f64, f64); DIMENSIONS],
+    rule: &'a DetectionRule,
+}
+
+pub fn optimise(
+    config: &TrenchConfig,
+    test_location: &TestLocation,
+    search_space: &SearchSpace,
+    strategy: &Strategy,
+    rule: &DetectionRule,
+) -> Result<Solution> {
+    let excavation = test_location.excavation(config);
+    if excavation.available.0.is_empty() {
+        return Err(anyhow!("Exclusion zones cover the whole LOE"));
+    }
+    let budget = match trench::with_equivalent_coverage(config, &excavation).distribution {
+        Distribution::Coverage(coverage) => coverage,
+        _ => {
+            return Err(anyhow!("Optimising requires a coverage or cost budget"));
+        }
+    };
+    let problem = Problem {
+        config,
+        excavation,
+        features: &test_location.features,
+        budget,
+        bounds: search_space.bounds(),
+        rule,
+    };
+    let solution = match *strategy {
+        Strategy::GridSearch { steps } => grid_search(&problem, steps),
+        Strategy::RandomRestart {
+            restarts,
+            iterations,
+            seed,
+        } => random_restart(&problem, restarts, iterations, seed),
+        Strategy::SimulatedAnnealing {
+            iterations,
+            initial_temperature,
+            cooling_rate,
+            seed,
+        } => simulated_annealing(
+            &problem,
+            iterations,
+            initial_temperature,
+            cooling_rate,
+            seed,
+        ),
+    };
+    solution.ok_or_else(|| anyhow!("No layout found within coverage budget of {:?}", budget))
+}
+
+pub fn test_location_from_prior(
+    test_location: TestLocation,
+    prior: &Prior,
+) -> Result<TestLocation> {
+    let limit_of_excavation = test_location.limit_of_excavation;
+    let bounding_rect = limit_of_excavation
+        .bounding_rect()
+        .ok_or_else(|| anyhow!("No prior features can be scattered over an empty LOE"))?;
+    let mut rng = StdRng::seed_from_u64(prior.seed);
+    let half_size = prior.feature_size / 2.0;
+    let mut features = Vec::new();
+    let mut attempts = 0;
+    while features.len() < prior.count && attempts < prior.count * 100 {
+        attempts += 1;
+        let centre = Point::new(
+            random_value((bounding_rect.min().x, bounding_rect.max().x), &mut rng),
+            random_value((bounding_rect.min().y, bounding_rect.max().y), &mut rng),
+        );
+        if !limit_of_excavation.contains(&centre) {
+            continue;
+        }
+        let exterior = vec![
+            coord! { x: centre.x() - half_size, y: centre.y() - half_size },
+            coord! { x: centre.x() + half_size, y: centre.y() - half_size },
+            coord! { x: centre.x() + half_size, y: centre.y() + half_size },
+            coord! { x: centre.x() - half_size, y: centre.y() + half_size },
+            coord! { x: centre.x() - half_size, y: centre.y() - half_size },
+        ];
+        features.push(ArchaeologicalFeature::new(
+            features.len() + 1,
+            FeatureGeometry::Polygon(MultiPolygon(vec![Polygon::new(
+                LineString(exterior),
+                vec![],
+            )])),
+            None,
+            Some("prior".to_string()),
+        ));
+    }
+    Ok(TestLocation {
+        site_name: test_location.site_name,
+        loe_i: test_location.loe_i,
+        period: test_location.period,
+        geology: test_location.geology,
+        limit_of_excavation,
+        exclusions: test_location.exclusions,
+        centre_line: test_location.centre_line,
+        focus: test_location.focus,
+        features,
+    })
+}
+
+fn config_of_point(config: &TrenchConfig, point: &[f64; DIMENSIONS]) -> Option<TrenchConfig> {
+    let structure = match config.structure {
+        structure @ (Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_)) => {
+            structure
+        }
+        Structure::Array(rectangle, array_config) => Structure::Array(
+            Rectangle::new(rectangle.width, point[LENGTH]),
+            Configuration {
+                base_angle: Degree(point[BASE_ANGLE]),
+                alternate_angle: Degree(point[ALTERNATE_ANGLE]),
+                ..array_config
+            },
+        ),
+    };
+    // the budget may be unattainable for some trench lengths and angles
+    TrenchConfig::new(structure, config.distribution)
+        .ok()
+        .map(|new_config| TrenchConfig {
+            structure: new_config.structure,
+            minimum_spacing: new_config.minimum_spacing,
+            ..*config
+        })
+}
+
+fn evaluate(problem: &Problem, point: &[f64; DIMENSIONS]) -> Option<Solution> {
+    let config = config_of_point(problem.config, point)?;
+    let spacing = point[SPACING]
+        * trench::estimate_spacing(
+            &config,
+            &problem.excavation.coverage_of_available(problem.budget),
+        );
+    if spacing < config.minimum_spacing {
+        return None;
+    }
+    let candidate = Candidate {
+        spacing,
+        rotation: Degree(point[ROTATION]),
+        offset: Offset::new(point[X_OFFSET], point[Y_OFFSET]),
+        length: point[LENGTH],
+        base_angle: Degree(point[BASE_ANGLE]),
+        alternate_angle: Degree(point[ALTERNATE_ANGLE]),
+    };
+    let layout = trench::create_layout(
+        &config,
+        &problem.excavation,
+        spacing,
+        candidate.rotation,
+        candidate.offset,
+    )
+    .ok()?;
+    let coverage = layout.metadata.map_or(0.0, |metadata| metadata.coverage);
+    // achieved coverage may exceed the budget by as much as the solver allows either side of it
+    if coverage > problem.budget.0 + problem.config.solver.tolerance {
+        return None;
+    }
+    let features_found = problem
+        .features
+        .iter()
+        .filter(|feature| intersects::detect(feature, &layout, problem.rule).hit)
+        .count();
+    let score = if problem.features.is_empty() {
+        0.0
+    } else {
+        features_found as f64 / problem.features.len() as f64
+    };
+    Some(Solution {
+        candidate,
+        layout,
+        score,
+        coverage: Percentage::new_from_percentage(coverage),
+    })
+}
+
+fn best_of(a: Option<Solution>, b: Option<Solution>) -> Option<Solution> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            if b.is_better_than(&a) {
+                Some(b)
+            } else {
+                Some(a)
+            }
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn grid_values(bounds: (f64, f64), periodic: bool, steps: usize) -> Vec<f64> {
+    let (min, max) = bounds;
+    if min == max || steps < 2 {
+        return vec![min];
+    }
+    // the upper bound of a periodic dimension is the same as its lower bound
+    let divisions = if periodic { steps } else { steps - 1 };
+    (0..steps)
+        .map(|i| min + (max - min) * i as f64 / divisions as f64)
+        .collect()
+}
+
+fn grid_search(problem: &Problem, steps: usize) -> Option<Solution> {
+    let values: Vec<Vec<f64>> = (0..DIMENSIONS)
+        .map(|dimension| grid_values(problem.bounds[dimension], PERIODIC[dimension], steps))
+        .collect();
+    let number_of_points: usize = values.iter().map(|v| v.len()).product();
+
+    (0..number_of_points)
+        .into_par_iter()
+        .map(|index| {
+            // decode the index into one value per dimension
+            let mut point = [0.0; DIMENSIONS];
+            let mut remainder = index;
+            for dimension in 0..DIMENSIONS {
+                point[dimension] = values[dimension][remainder % values[dimension].len()];
+                remainder /= values[dimension].len();
+            }
+            evaluate(problem, &point)
+        })
+        .reduce(|| None, best_of)
+}
+
+fn random_point(bounds: &[(f64, f64); DIMENSIONS], rng: &mut StdRng) -> [f64; DIMENSIONS] {
+    let mut point = [0.0; DIMENSIONS];
+    for (dimension, bounds) in bounds.iter().enumerate() {
+        point[dimension] = random_value(*bounds, rng);
+    }
+    point
+}
+
+// an empty range, such as a fixed parameter or the width of an LOE that is a line, has one value
+fn random_value((min, max): (f64, f64), rng: &mut StdRng) -> f64 {
+    if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+fn neighbour(
+    point: &[f64; DIMENSIONS],
+    bounds: &[(f64, f64); DIMENSIONS],
+    rng: &mut StdRng,
+) -> [f64; DIMENSIONS] {
+    let mut next = *point;
+    for (dimension, (min, max)) in bounds.iter().enumerate() {
+        if min == max {
+            continue;
+        }
+        let range = max - min;
+        let value = point[dimension] + rng.gen_range(-STEP_SIZE..STEP_SIZE) * range;
+        next[dimension] = if PERIODIC[dimension] {
+            min + (value - min).rem_euclid(range)
+        } else {
+            value.clamp(*min, *max)
+        };
+    }
+    next
+}
+
+fn hill_climb(problem: &Problem, iterations: usize, seed: u64) -> Option<Solution> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut point = random_point(&problem.bounds, &mut rng);
+    let mut current = evaluate(problem, &point);
+    for _ in 0..iterations {
+        let next_point = match current {
+            Some(_) => neighbour(&point, &problem.bounds, &mut rng),
+            // keep sampling until a layout within budget is found
+            None => random_point(&problem.bounds, &mut rng),
+        };
+        let next = evaluate(problem, &next_point);
+        let improved = match (&next, &current) {
+            (Some(next), Some(current)) => next.is_better_than(current),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if improved {
+            point = next_point;
+            current = next;
+        }
+    }
+    current
+}
+
+fn random_restart(
+    problem: &Problem,
+    restarts: usize,
+    iterations: usize,
+    seed: u64,
+) -> Option<Solution> {
+    (0..restarts as u64)
+        .into_par_iter()
+        .map(|restart| hill_climb(problem, iterations, seed.wrapping_add(restart)))
+        .reduce(|| None, best_of)
+}
+
+fn simulated_annealing(
+    problem: &Problem,
+    iterations: usize,
+    initial_temperature: f64,
+    cooling_rate: f64,
+    seed: u64,
+) -> Option<Solution> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut point = random_point(&problem.bounds, &mut rng);
+    let mut best = evaluate(problem, &point);
+    let mut current_score = best.as_ref().map(|solution| solution.score);
+    let mut temperature = initial_temperature;
+
+    for _ in 0..iterations {
+        let next_point = match current_score {
+            Some(_) => neighbour(&point, &problem.bounds, &mut rng),
+            None => random_point(&problem.bounds, &mut rng),
+        };
+        if let Some(next) = evaluate(problem, &next_point) {
+            let accept = match current_score {
+                Some(current_score) => {
+                    next.score >= current_score
+                        || rng.gen::<f64>() < ((next.score - current_score) / temperature).exp()
+                }
+                None => true,
+            };
+            if accept {
+                point = next_point;
+                current_score = Some(next.score);
+                best = best_of(best, Some(next));
+            }
+        }
+        temperature *= cooling_rate;
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(limit_of_excavation: MultiPolygon) -> TestLocation {
+        TestLocation {
+            site_name: "site".to_string(),
+            loe_i: "0".to_string(),
+            period: None,
+            geology: None,
+            limit_of_excavation,
+            exclusions: MultiPolygon(vec![]),
+            centre_line: None,
+            focus: None,
+            features: Vec::new(),
+        }
+    }
+
+    fn prior() -> Prior {
+        Prior {
+            feature_size: 2.0,
+            count: 10,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn prior_features_cannot_be_scattered_over_an_empty_loe() {
+        assert!(test_location_from_prior(location(MultiPolygon(vec![])), &prior()).is_err());
+    }
+
+    #[test]
+    fn prior_features_miss_an_loe_with_no_width() {
+        let line = Polygon::new(
+            LineString::from(vec![(0.0, 0.0), (0.0, 10.0), (0.0, 0.0)]),
+            vec![],
+        );
+        let located = test_location_from_prior(location(MultiPolygon(vec![line])), &prior());
+        assert!(located.unwrap().features.is_empty());
+    }
+
+    fn square(size: f64) -> MultiPolygon {
+        MultiPolygon(vec![Polygon::new(
+            LineString::from(vec![
+                (0.0, 0.0),
+                (size, 0.0),
+                (size, size),
+                (0.0, size),
+                (0.0, 0.0),
+            ]),
+            vec![],
+        )])
+    }
+
+    // a 200m square LOE with features scattered over it
+    fn scattered_location() -> TestLocation {
+        let prior = Prior {
+            feature_size: 2.0,
+            count: 40,
+            seed: 1,
+        };
+        test_location_from_prior(location(square(200.0)), &prior).unwrap()
+    }
+
+    fn config() -> TrenchConfig {
+        TrenchConfig::continuous(
+            2.0,
+            Distribution::Coverage(Percentage::new_from_percentage(5.0)),
+        )
+        .unwrap()
+    }
+
+    fn solution(score: f64, coverage: f64) -> Solution {
+        Solution {
+            candidate: Candidate {
+                spacing: 40.0,
+                rotation: Degree(0.0),
+                offset: Offset::new(0.0, 0.0),
+                length: 0.0,
+                base_angle: Degree(0.0),
+                alternate_angle: Degree(0.0),
+            },
+            layout: TrenchLayout {
+                trenches: MultiPolygon(vec![]),
+                metadata: None,
+            },
+            score,
+            coverage: Percentage::new_from_percentage(coverage),
+        }
+    }
+
+    #[test]
+    fn the_better_solution_finds_more_then_covers_less() {
+        let best = best_of(Some(solution(0.5, 4.0)), Some(solution(0.6, 5.0))).unwrap();
+        assert_eq!(best.score, 0.6);
+        let best = best_of(Some(solution(0.5, 5.0)), Some(solution(0.5, 4.0))).unwrap();
+        assert_eq!(best.coverage.0, 4.0);
+        assert!(best_of(None, Some(solution(0.5, 5.0))).is_some());
+        assert!(best_of(None, None).is_none());
+    }
+
+    #[test]
+    fn every_strategy_keeps_within_the_coverage_budget() {
+        let config = config();
+        let location = scattered_location();
+        let strategies = [
+            Strategy::GridSearch { steps: 2 },
+            Strategy::RandomRestart {
+                restarts: 2,
+                iterations: 10,
+                seed: 0,
+            },
+            Strategy::SimulatedAnnealing {
+                iterations: 20,
+                initial_temperature: 0.05,
+                cooling_rate: 0.99,
+                seed: 0,
+            },
+        ];
+        for strategy in strategies.iter() {
+            let solution = optimise(
+                &config,
+                &location,
+                &SearchSpace::new(&config),
+                strategy,
+                &DetectionRule::default(),
+            )
+            .unwrap();
+            assert!(solution.coverage.0 <= 5.0 + config.solver.tolerance);
+            assert!(!solution.layout.trenches.0.is_empty());
+        }
+    }
+
+    #[test]
+    fn grid_search_picks_the_best_point_of_the_grid() {
+        let config = config();
+        let location = scattered_location();
+        let problem = Problem {
+            config: &config,
+            excavation: location.excavation(&config),
+            features: &location.features,
+            budget: Percentage::new_from_percentage(5.0),
+            bounds: SearchSpace::new(&config).bounds(),
+            rule: &DetectionRule::default(),
+        };
+        let values: Vec<Vec<f64>> = (0..DIMENSIONS)
+            .map(|dimension| grid_values(problem.bounds[dimension], PERIODIC[dimension], 2))
+            .collect();
+        let mut best_score: f64 = 0.0;
+        for &spacing in values[SPACING].iter() {
+            for &rotation in values[ROTATION].iter() {
+                for &x_offset in values[X_OFFSET].iter() {
+                    let mut point = [0.0; DIMENSIONS];
+                    point[SPACING] = spacing;
+                    point[ROTATION] = rotation;
+                    point[X_OFFSET] = x_offset;
+                    if let Some(solution) = evaluate(&problem, &point) {
+                        best_score = best_score.max(solution.score);
+                    }
+                }
+            }
+        }
+        let solution = grid_search(&problem, 2).unwrap();
+        assert_eq!(solution.score, best_score);
+        assert!(best_score > 0.0);
+    }
+}
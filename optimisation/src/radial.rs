@@ -0,0 +1,383 @@
+use geo::{Centroid, Contains, Coord, LineString, MultiPolygon, Point, Polygon, Rotate, Translate};
+use rayon::prelude::*;
+use std::f64::consts::PI;
+use trenching_optimisation::{
+    CoverageFailure, Degree, Distribution, Excavation, LayoutPlacement, Offset, Percentage,
+    Structure, TrenchConfig, TrenchLayout,
+};
+
+use crate::trench;
+
+// sides of the polygons rings are drawn with
+const RING_SEGMENTS: usize = 180;
+
+// the point spokes radiate from and rings are drawn around, and the farthest the available area
+// reaches from it
+struct Focus {
+    point: Point,
+    reach: f64,
+}
+
+// a layout for each offset, turning spokes or moving rings out by a fraction of the spacing,
+// which are not rotated
+pub fn create_layouts(config: &TrenchConfig, excavation: &Excavation) -> Option<Vec<TrenchLayout>> {
+    let focus = Focus::of_excavation(excavation)?;
+    match config.distribution {
+        Distribution::Spacing(spacing) => {
+            Some(layouts_at_spacing(config, excavation, &focus, spacing))
+        }
+        // a number of spokes or rings rather than trenches on a lattice
+        Distribution::Count(count) => Some(layouts_at_spacing(
+            config,
+            excavation,
+            &focus,
+            focus.spacing_of_count(config.structure, count),
+        )),
+        Distribution::Coverage(coverage) => {
+            let results = config
+                .get_offsets()
+                .into_par_iter()
+                .map(|offset| {
+                    let result = solve_coverage(config, excavation, &focus, coverage, offset);
+                    (0, offset, result)
+                })
+                .collect();
+            trench::collect_solutions(config, coverage, results)
+        }
+        Distribution::Cost(_) => create_layouts(
+            &trench::with_equivalent_coverage(config, excavation),
+            excavation,
+        ),
+    }
+}
+
+pub fn create_single_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    offset: Offset,
+) -> Result<TrenchLayout, CoverageFailure> {
+    let focus = Focus::of_excavation(excavation).ok_or(CoverageFailure::NothingAvailable)?;
+    match config.distribution {
+        Distribution::Spacing(spacing) => Ok(focus.layout(config, excavation, spacing, offset)),
+        Distribution::Count(count) => Ok(focus.layout(
+            config,
+            excavation,
+            focus.spacing_of_count(config.structure, count),
+            offset,
+        )),
+        Distribution::Coverage(coverage) => {
+            solve_coverage(config, excavation, &focus, coverage, offset)
+                .map(|solution| solution.layout)
+        }
+        Distribution::Cost(_) => create_single_layout(
+            &trench::with_equivalent_coverage(config, excavation),
+            excavation,
+            offset,
+        ),
+    }
+}
+
+// single layout at a fixed spacing and offset, empty when nothing is available
+pub fn create_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    spacing: f64,
+    offset: Offset,
+) -> TrenchLayout {
+    match Focus::of_excavation(excavation) {
+        Some(focus) => focus.layout(config, excavation, spacing, offset),
+        None => trench::placed_layout(
+            MultiPolygon(vec![]),
+            excavation,
+            config,
+            LayoutPlacement {
+                rotation: Degree(0.0),
+                spacing,
+                offset,
+            },
+        ),
+    }
+}
+
+fn layouts_at_spacing(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    focus: &Focus,
+    spacing: f64,
+) -> Vec<TrenchLayout> {
+    config
+        .get_offsets()
+        .into_par_iter()
+        .map(|offset| focus.layout(config, excavation, spacing, offset))
+        .collect()
+}
+
+// beyond the spacing of a single spoke or ring the pattern can only lose it
+fn solve_coverage(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    focus: &Focus,
+    coverage: Percentage,
+    offset: Offset,
+) -> Result<trench::CoverageSolution, CoverageFailure> {
+    let estimated_spacing =
+        trench::estimate_spacing(config, &excavation.coverage_of_available(coverage));
+    trench::solve_coverage(
+        config,
+        excavation,
+        focus.spacing_of_count(config.structure, 1) * 2.0,
+        estimated_spacing,
+        coverage.0,
+        0,
+        offset,
+        |config, spacing| focus.shortened_pattern(config, spacing, offset),
+    )
+    .map(|solution| trench::CoverageSolution {
+        layout: focus.count_clipped_rings(solution.layout, config, excavation, offset),
+        ..solution
+    })
+}
+
+impl Focus {
+    // the supplied focus, or the centroid of what is available
+    fn of_excavation(excavation: &Excavation) -> Option<Self> {
+        let point = match excavation.focus {
+            Some(point) => point,
+            None => excavation.available.centroid()?,
+        };
+        let reach = trench::get_max_distance_from_centroid(point, &excavation.available);
+        Some(Focus { point, reach })
+    }
+
+    // spokes a spacing apart round the circle they reach across, which may not be a whole number
+    fn spokes_at(&self, spacing: f64) -> f64 {
+        2.0 * PI * self.reach / spacing
+    }
+
+    // the spacing giving a number of spokes, or of rings out to the farthest reach
+    fn spacing_of_count(&self, structure: Structure, count: usize) -> f64 {
+        let count = count.max(1) as f64;
+        match structure {
+            Structure::Spokes(_) => 2.0 * PI * self.reach / count,
+            _ => self.reach / count,
+        }
+    }
+
+    fn layout(
+        &self,
+        config: &TrenchConfig,
+        excavation: &Excavation,
+        spacing: f64,
+        offset: Offset,
+    ) -> TrenchLayout {
+        let trench_pattern = self.pattern(config, spacing, offset);
+        let layout = trench::placed_layout(
+            trench::fit_to_excavation(&trench_pattern, excavation, config),
+            excavation,
+            config,
+            LayoutPlacement {
+                rotation: Degree(0.0),
+                spacing,
+                offset,
+            },
+        );
+        self.count_clipped_rings(layout, config, excavation, offset)
+    }
+
+    // whole rings are left as they were, so the rest of what is dug is rings or pieces of rings
+    // cut by the edge
+    fn count_clipped_rings(
+        &self,
+        layout: TrenchLayout,
+        config: &TrenchConfig,
+        excavation: &Excavation,
+        offset: Offset,
+    ) -> TrenchLayout {
+        let (Structure::Rings(_), Some(mut metadata)) = (config.structure, layout.metadata) else {
+            return layout;
+        };
+        let whole_rings = self
+            .pattern(config, metadata.spacing, offset)
+            .iter()
+            .filter(|ring| excavation.available.contains(*ring))
+            .count();
+        metadata.clipped_trenches = metadata.trenches.saturating_sub(whole_rings);
+        TrenchLayout {
+            metadata: Some(metadata),
+            ..layout
+        }
+    }
+
+    // the nearest whole number of spokes, and rings whose middle is within half a width of the
+    // focus are filled in
+    fn pattern(&self, config: &TrenchConfig, spacing: f64, offset: Offset) -> MultiPolygon {
+        match config.structure {
+            Structure::Spokes(line) => {
+                self.spokes(line.width, self.spokes_at(spacing).round(), offset)
+            }
+            Structure::Rings(line) => {
+                let rings = (self.reach / spacing).ceil() as i32 + 1;
+                MultiPolygon(
+                    (0..rings)
+                        .map(|i| (i as f64 + offset.x) * spacing)
+                        .filter(|&radius| radius > 0.0 && radius - line.width / 2.0 < self.reach)
+                        .map(|radius| {
+                            let outside = self.circle(radius + line.width / 2.0);
+                            let inside = radius - line.width / 2.0;
+                            if inside > 0.0 {
+                                let mut hole = self.circle(inside);
+                                hole.0.reverse();
+                                Polygon::new(outside, vec![hole])
+                            } else {
+                                Polygon::new(outside, vec![])
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            // not laid around a focus
+            Structure::Parallel(_) | Structure::Array(_, _) => MultiPolygon(vec![]),
+        }
+    }
+
+    // the pattern with as many spokes as the spacing gives, shortened from the focus when it is
+    // not a whole number, so coverage comes and goes smoothly as the spacing is searched
+    fn shortened_pattern(
+        &self,
+        config: &TrenchConfig,
+        spacing: f64,
+        offset: Offset,
+    ) -> MultiPolygon {
+        match config.structure {
+            Structure::Spokes(line) => self.spokes(line.width, self.spokes_at(spacing), offset),
+            _ => self.pattern(config, spacing, offset),
+        }
+    }
+
+    // spokes evenly round the focus out to the farthest reach, starting where they stop
+    // overlapping their neighbours so the focus itself is left undug, with a fraction of a
+    // spoke shared out by shortening them all
+    fn spokes(&self, width: f64, spokes: f64, offset: Offset) -> MultiPolygon {
+        let count = spokes.ceil().max(1.0) as usize;
+        let angle = 360.0 / count as f64;
+        let start = if count > 2 {
+            width / 2.0 / (PI / count as f64).tan()
+        } else {
+            0.0
+        };
+        if start >= self.reach {
+            return MultiPolygon(vec![]);
+        }
+        let length = (self.reach - start) * spokes.min(count as f64) / count as f64;
+        let spoke = trench::plot_trench(
+            self.point.translate(0.0, self.reach - length / 2.0),
+            width,
+            length,
+            Degree(0.0),
+        );
+        MultiPolygon(
+            (0..count)
+                .map(|i| spoke.rotate_around_point((i as f64 + offset.x) * angle, self.point))
+                .collect(),
+        )
+    }
+
+    // anticlockwise and closed
+    fn circle(&self, radius: f64) -> LineString {
+        LineString(
+            (0..=RING_SEGMENTS)
+                .map(|i| {
+                    let angle = 2.0 * PI * (i % RING_SEGMENTS) as f64 / RING_SEGMENTS as f64;
+                    Coord {
+                        x: self.point.x() + radius * angle.cos(),
+                        y: self.point.y() + radius * angle.sin(),
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{Area, BoundingRect, EuclideanDistance};
+    use trenching_optimisation::CoverageBasis;
+
+    fn focus() -> Focus {
+        Focus {
+            point: Point::new(0.0, 0.0),
+            reach: 100.0,
+        }
+    }
+
+    fn square(size: f64) -> MultiPolygon {
+        let square = geo::Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: size, y: size });
+        MultiPolygon(vec![square.to_polygon()])
+    }
+
+    #[test]
+    fn spokes_reach_the_edge_a_spacing_apart() {
+        let focus = focus();
+        let config = TrenchConfig::spokes(2.0, Distribution::Count(8)).unwrap();
+        let spacing = focus.spacing_of_count(config.structure, 8);
+        assert!((focus.spokes_at(spacing) - 8.0).abs() < 1e-12);
+        let spokes = focus.pattern(&config, spacing, Offset::new(0.0, 0.0));
+        assert_eq!(spokes.0.len(), 8);
+        for spoke in spokes.iter() {
+            let far_end = spoke
+                .exterior()
+                .points()
+                .map(|point| point.euclidean_distance(&focus.point))
+                .fold(0.0, f64::max);
+            assert!(far_end >= 100.0);
+            // the focus is left undug
+            assert!(spoke.euclidean_distance(&focus.point) > 0.0);
+        }
+        // the first spoke runs north, and the offset turns them by a fraction of a spacing
+        let north = spokes.0[0].bounding_rect().unwrap();
+        assert!(north.center().x.abs() < 1e-9 && north.center().y > 0.0);
+        let turned = focus.pattern(&config, spacing, Offset::new(0.5, 0.0));
+        let turned = turned.0[0].centroid().unwrap();
+        assert!((turned.y().atan2(turned.x()).to_degrees() - (90.0 + 22.5)).abs() < 1e-6);
+        // part of a spoke is shared out by shortening them all
+        let shortened =
+            focus.shortened_pattern(&config, spacing * 8.0 / 7.5, Offset::new(0.0, 0.0));
+        assert_eq!(shortened.0.len(), 8);
+        assert!((shortened.unsigned_area() / spokes.unsigned_area() - 7.5 / 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rings_are_a_spacing_apart_out_to_the_reach() {
+        let focus = focus();
+        let config = TrenchConfig::rings(2.0, Distribution::Spacing(30.0)).unwrap();
+        let ring_area = |radius: f64| 2.0 * PI * radius * 2.0;
+        let rings = focus.pattern(&config, 30.0, Offset::new(0.0, 0.0));
+        assert_eq!(rings.0.len(), 3);
+        for (ring, radius) in rings.iter().zip([30.0, 60.0, 90.0]) {
+            assert_eq!(ring.interiors().len(), 1);
+            assert!((ring.unsigned_area() / ring_area(radius) - 1.0).abs() < 0.01);
+        }
+        // moved out by half a spacing, the last ring is beyond the reach
+        let moved = focus.pattern(&config, 30.0, Offset::new(0.5, 0.0));
+        assert_eq!(moved.0.len(), 3);
+        assert!((moved.0[0].unsigned_area() / ring_area(15.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rings_cut_by_the_edge_are_counted_as_clipped() {
+        let excavation = Excavation::new(
+            &square(200.0),
+            &MultiPolygon(vec![]),
+            0.0,
+            CoverageBasis::Available,
+        );
+        let config = TrenchConfig::rings(2.0, Distribution::Spacing(40.0)).unwrap();
+        let layout = create_layout(&config, &excavation, 40.0, Offset::new(0.0, 0.0));
+        let metadata = layout.metadata.unwrap();
+        // rings of 40m and 80m are whole, that of 120m is cut into the corners, and one of 160m
+        // would be beyond them
+        assert_eq!(metadata.trenches, 6);
+        assert_eq!(metadata.clipped_trenches, 4);
+    }
+}
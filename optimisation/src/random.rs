@@ -0,0 +1,422 @@
+use geo::{
+    coord, Area, BoundingRect, Centroid, Contains, Intersects, MultiPolygon, Point, Polygon, Rect,
+    Rotate,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use trenching_optimisation::{
+    CoverageFailure, Degree, Distribution, Excavation, LayoutPlacement, Offset, Placement,
+    Structure, TrenchConfig, TrenchLayout,
+};
+
+use crate::trench;
+
+// failed tries in a row before the LOE, or a stratum, is taken to be full
+const ATTEMPTS: usize = 1000;
+
+// when to stop placing trenches uniformly at random
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Trenches(usize),
+    Coverage(f64), // percentage of the coverage area
+}
+
+// a realisation for each seed from the first, each at a random rotation unless the orientation
+// fixes it, which is still drawn so a seed places the same trenches
+pub fn create_layouts(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    seed: u64,
+    layouts: usize,
+) -> Option<Vec<TrenchLayout>> {
+    let centroid = excavation.available.centroid()?;
+    let fixed_rotation = config.get_fixed_rotation(excavation);
+    let mut failures = Vec::new();
+    let layouts: Vec<TrenchLayout> = (0..layouts)
+        .into_par_iter()
+        .map(|i| {
+            let seed = seed.wrapping_add(i as u64);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let rotation = rng.gen_range(0..config.structure.get_rotational_symmetry());
+            let rotation = fixed_rotation.unwrap_or(rotation);
+            (
+                seed,
+                create_layout(config, excavation, centroid, rotation, &mut rng),
+            )
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .filter_map(|(seed, result)| match result {
+            Ok(solution) => Some(solution.layout),
+            Err(failure) => {
+                failures.push((format!("seed {}", seed), failure));
+                None
+            }
+        })
+        .collect();
+    trench::report_failures(&config.solver, layouts.len() + failures.len(), &failures);
+    (!layouts.is_empty()).then_some(layouts)
+}
+
+// the realisation of a single seed at a chosen rotation
+pub fn create_single_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    seed: u64,
+    rotation: i32,
+) -> Result<TrenchLayout, CoverageFailure> {
+    let centroid = excavation
+        .available
+        .centroid()
+        .ok_or(CoverageFailure::NothingAvailable)?;
+    let mut rng = StdRng::seed_from_u64(seed);
+    create_layout(config, excavation, centroid, rotation, &mut rng).map(trench::solution_layout)
+}
+
+fn create_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    centroid: Point,
+    rotation: i32,
+    rng: &mut StdRng,
+) -> Result<trench::CoverageSolution, CoverageFailure> {
+    let reach = trench::get_max_distance_from_centroid(centroid, &excavation.available);
+    if let Placement::StratifiedRandom { .. } = config.placement {
+        // strata on the edge are often left empty, so the size of a stratum is searched for as the
+        // lattice's spacing is, each try drawing the same points from the seed
+        let start = rng.clone();
+        return trench::solve_coverage(
+            config,
+            excavation,
+            reach * 4.0,
+            stratum_size(config, excavation),
+            lattice_coverage(config, excavation),
+            rotation,
+            Offset::new(0.0, 0.0),
+            |config, spacing| {
+                place_stratified(
+                    config,
+                    excavation,
+                    centroid,
+                    reach,
+                    rotation,
+                    spacing,
+                    &mut start.clone(),
+                )
+            },
+        );
+    }
+    let trenches = place_uniformly(
+        config,
+        excavation,
+        reach,
+        rotation,
+        target(config, excavation),
+        rng,
+    );
+    // the side of the square each trench has to itself
+    let spacing = (excavation.available.unsigned_area() / trenches.0.len().max(1) as f64).sqrt();
+    let layout = trench::placed_layout(
+        trenches,
+        excavation,
+        config,
+        LayoutPlacement {
+            rotation: Degree(rotation as f64),
+            spacing,
+            offset: Offset::new(0.0, 0.0),
+        },
+    );
+    Ok(trench::CoverageSolution {
+        coverage: layout.metadata.map_or(0.0, |metadata| metadata.coverage),
+        layout,
+        length: None,
+    })
+}
+
+// a spacing places as much trench as the lattice would at it
+fn target(config: &TrenchConfig, excavation: &Excavation) -> Target {
+    match config.distribution {
+        Distribution::Count(trenches) => Target::Trenches(trenches),
+        _ => Target::Coverage(lattice_coverage(config, excavation)),
+    }
+}
+
+// the coverage of the lattice at a spacing, or of the trenches a count or budget gives
+fn lattice_coverage(config: &TrenchConfig, excavation: &Excavation) -> f64 {
+    match config.distribution {
+        Distribution::Spacing(spacing) => {
+            config.structure.get_coverage_from_spacing(spacing).0
+                * excavation.available.unsigned_area()
+                / excavation.coverage_area
+        }
+        Distribution::Coverage(coverage) => coverage.0,
+        Distribution::Count(_) | Distribution::Cost(_) => lattice_coverage(
+            &trench::with_equivalent_coverage(config, excavation),
+            excavation,
+        ),
+    }
+}
+
+// a stratum holds one trench, so is a square the area of a cell of the lattice holding one
+fn stratum_size(config: &TrenchConfig, excavation: &Excavation) -> f64 {
+    let spacing = match config.distribution {
+        Distribution::Spacing(spacing) => spacing,
+        Distribution::Coverage(coverage) => {
+            trench::estimate_spacing(config, &excavation.coverage_of_available(coverage))
+        }
+        Distribution::Count(_) | Distribution::Cost(_) => {
+            return stratum_size(
+                &trench::with_equivalent_coverage(config, excavation),
+                excavation,
+            );
+        }
+    };
+    match config.structure {
+        Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => spacing,
+        Structure::Array(_, array_config) => {
+            let cells = if array_config.separated { 2.0 } else { 1.0 };
+            spacing * (cells * array_config.lattice.row_spacing()).sqrt()
+        }
+    }
+}
+
+// the trench at a point, continuous trenches long enough to cross the LOE from anywhere in it,
+// and array trenches taking the base and alternate angles in turn
+fn trench_at(structure: Structure, centre: Point, reach: f64, rotation: i32, i: usize) -> Polygon {
+    match structure {
+        Structure::Parallel(line) | Structure::Spokes(line) | Structure::Rings(line) => {
+            trench::plot_trench(centre, line.width, reach * 4.0, Degree(rotation as f64))
+        }
+        Structure::Array(rectangle, array_config) => {
+            let angle = if i.is_multiple_of(2) {
+                array_config.base_angle
+            } else {
+                array_config.alternate_angle
+            };
+            trench::plot_trench(
+                centre,
+                rectangle.width,
+                rectangle.length,
+                Degree(angle.0 + rotation as f64),
+            )
+        }
+    }
+}
+
+fn random_point(bounds: Rect, rng: &mut StdRng) -> Point {
+    Point::new(
+        rng.gen_range(bounds.min().x..=bounds.max().x),
+        rng.gen_range(bounds.min().y..=bounds.max().y),
+    )
+}
+
+// a trench clear of those placed, cut to the excavation and kept when anything is left of it
+fn try_trench(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    trench: Polygon,
+    placed: &[Polygon],
+) -> Option<MultiPolygon> {
+    if placed.iter().any(|p| p.intersects(&trench)) {
+        return None;
+    }
+    let pieces = trench::fit_to_excavation(&MultiPolygon(vec![trench]), excavation, config);
+    (!pieces.0.is_empty()).then_some(pieces)
+}
+
+fn place_uniformly(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    reach: f64,
+    rotation: i32,
+    target: Target,
+    rng: &mut StdRng,
+) -> MultiPolygon {
+    let bounds = excavation.available.bounding_rect().unwrap();
+    let mut placed: Vec<Polygon> = Vec::new(); // whole, so trenches never overlap
+    let mut trenches: Vec<Polygon> = Vec::new(); // as cut to the excavation
+    let mut area = 0.0;
+    let mut failures = 0;
+    while failures < ATTEMPTS {
+        let done = match target {
+            Target::Trenches(count) => placed.len() >= count,
+            Target::Coverage(coverage) => area / excavation.coverage_area * 100.0 >= coverage,
+        };
+        if done {
+            break;
+        }
+        let centre = random_point(bounds, rng);
+        if !excavation.available.contains(&centre) {
+            failures += 1;
+            continue;
+        }
+        let trench = trench_at(config.structure, centre, reach, rotation, placed.len());
+        match try_trench(config, excavation, trench.clone(), &placed) {
+            Some(pieces) => {
+                area += pieces.unsigned_area();
+                trenches.extend(pieces.0);
+                placed.push(trench);
+                failures = 0;
+            }
+            None => failures += 1,
+        }
+    }
+    MultiPolygon(trenches)
+}
+
+// cells of a lattice at the rotation each given a trench at a random point of it, or for
+// continuous trenches strips each given a line at a random offset across it, the whole trenches
+// kept being returned for the solver to cut, as cutting a piece again can trip the sweep in geo
+#[allow(clippy::too_many_arguments)]
+fn place_stratified(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    centroid: Point,
+    reach: f64,
+    rotation: i32,
+    stratum_size: f64,
+    rng: &mut StdRng,
+) -> MultiPolygon {
+    let n = (reach / stratum_size).ceil() as i32;
+    let (rows, stratum_height) = match config.structure {
+        Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => (0..1, reach * 2.0),
+        Structure::Array(_, _) => (-n..n + 1, stratum_size),
+    };
+    let mut placed: Vec<Polygon> = Vec::new();
+    for (x_index, x) in (-n..n + 1).enumerate() {
+        for (y_index, y) in rows.clone().enumerate() {
+            let min = coord! {
+                x: centroid.x() + (x as f64 - 0.5) * stratum_size,
+                y: centroid.y() + (y as f64 - 0.5) * stratum_height,
+            };
+            let stratum = Rect::new(
+                min,
+                coord! { x: min.x + stratum_size, y: min.y + stratum_height },
+            );
+            let stratum_polygon = stratum
+                .to_polygon()
+                .rotate_around_point(rotation as f64, centroid);
+            if !excavation.available.intersects(&stratum_polygon) {
+                continue;
+            }
+            let i = x_index + y_index;
+            // a trench outside the excavation leaves the stratum empty, so those on the edge hold
+            // a trench as often as they are inside it, while overlapping trenches are tried again
+            let kept = (0..ATTEMPTS)
+                .map(|_| {
+                    let mut point = random_point(stratum, rng);
+                    if let Structure::Parallel(_) = config.structure {
+                        // only the offset across the strip matters to a line
+                        point = Point::new(point.x(), centroid.y());
+                    }
+                    let centre = point.rotate_around_point(rotation as f64, centroid);
+                    (
+                        centre,
+                        trench_at(config.structure, centre, reach, rotation, i),
+                    )
+                })
+                .take_while(|(centre, trench)| match config.structure {
+                    Structure::Parallel(_) | Structure::Spokes(_) | Structure::Rings(_) => {
+                        excavation.available.intersects(trench)
+                    }
+                    Structure::Array(_, _) => excavation.available.contains(centre),
+                })
+                .find(|(_, trench)| {
+                    try_trench(config, excavation, trench.clone(), &placed).is_some()
+                });
+            if let Some((_, trench)) = kept {
+                placed.push(trench);
+            }
+        }
+    }
+    MultiPolygon(placed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::LineString;
+    use trenching_optimisation::{CoverageBasis, Percentage};
+
+    fn square(size: f64) -> MultiPolygon {
+        MultiPolygon(vec![Polygon::new(
+            LineString::from(vec![
+                (0.0, 0.0),
+                (size, 0.0),
+                (size, size),
+                (0.0, size),
+                (0.0, 0.0),
+            ]),
+            vec![],
+        )])
+    }
+
+    fn excavation(size: f64) -> Excavation {
+        Excavation::new(
+            &square(size),
+            &MultiPolygon(vec![]),
+            0.0,
+            CoverageBasis::Available,
+        )
+    }
+
+    #[test]
+    fn seeds_wrap_around_rather_than_overflowing() {
+        let config = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0)).unwrap();
+        let layouts = create_layouts(&config, &excavation(100.0), u64::MAX, 2).unwrap();
+        assert_eq!(layouts.len(), 2);
+    }
+
+    fn assert_no_overlaps(layout: &TrenchLayout) {
+        let trenches = &layout.trenches.0;
+        for (i, trench) in trenches.iter().enumerate() {
+            assert!(!trenches[i + 1..]
+                .iter()
+                .any(|other| other.intersects(trench)));
+        }
+    }
+
+    #[test]
+    fn random_trenches_never_overlap() {
+        let grid = |distribution| {
+            TrenchConfig::standard_grid(
+                2.0,
+                30.0,
+                trenching_optimisation::array::Lattice::Square,
+                distribution,
+            )
+            .unwrap()
+        };
+        let excavation = excavation(120.0);
+        // crowded enough that trenches are often drawn on top of each other
+        let uniform = grid(Distribution::Count(60)).with_placement(Placement::Random {
+            seed: 1,
+            layouts: 3,
+        });
+        for layout in create_layouts(&uniform, &excavation, 1, 3).unwrap() {
+            assert_eq!(layout.trenches.0.len(), 60);
+            assert_no_overlaps(&layout);
+        }
+        let coverage = Percentage::new_from_percentage(10.0);
+        let stratified =
+            grid(Distribution::Coverage(coverage)).with_placement(Placement::StratifiedRandom {
+                seed: 1,
+                layouts: 3,
+            });
+        for layout in create_layouts(&stratified, &excavation, 1, 3).unwrap() {
+            assert_no_overlaps(&layout);
+            let achieved = layout.metadata.unwrap().coverage;
+            assert!((achieved - 10.0).abs() <= stratified.solver.tolerance);
+        }
+    }
+
+    #[test]
+    fn a_seed_places_the_same_trenches() {
+        let config = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0)).unwrap();
+        let excavation = excavation(100.0);
+        let layout = |seed| create_single_layout(&config, &excavation, seed, 30).unwrap();
+        assert_eq!(layout(4).trenches, layout(4).trenches);
+        assert_ne!(layout(4).trenches, layout(5).trenches);
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Result};
+use geo::{Coord, MinimumRotatedRect, Polygon};
+use std::path::Path;
+
+use crate::TrenchLayout;
+
+// coordinate lists for setting a layout out on site, in the coordinates of the LOE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingOutFormat {
+    Trenches,     // one row per trench with its corners and centre line
+    SurveyPoints, // one named point per row, for loading into a total station or GPS
+}
+
+// a trench numbered as in the GeoJSON export, as the rectangle enclosing what is left
+// after cutting it to the LOE
+#[derive(Debug, Clone, Copy)]
+pub struct SettingOut {
+    pub trench_id: usize,
+    pub corners: [Coord; 4],
+    pub centre_line: (Coord, Coord),
+}
+
+impl SettingOut {
+    pub fn new(trench_id: usize, trench: &Polygon) -> Result<Self> {
+        let rectangle = trench
+            .minimum_rotated_rect()
+            .ok_or_else(|| anyhow!("Trench {} has no area to set out", trench_id))?;
+        let ring = &rectangle.exterior().0;
+        let corners = [ring[0], ring[1], ring[2], ring[3]];
+        // the centre line joins the middles of the short ends
+        let centre_line = if distance(corners[0], corners[1]) < distance(corners[1], corners[2]) {
+            (
+                midpoint(corners[0], corners[1]),
+                midpoint(corners[2], corners[3]),
+            )
+        } else {
+            (
+                midpoint(corners[1], corners[2]),
+                midpoint(corners[3], corners[0]),
+            )
+        };
+        Ok(SettingOut {
+            trench_id,
+            corners,
+            centre_line,
+        })
+    }
+}
+
+fn distance(a: Coord, b: Coord) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+fn midpoint(a: Coord, b: Coord) -> Coord {
+    (a + b) / 2.0
+}
+
+// millimetres are as precise as setting out gets
+fn format_coordinate(value: f64) -> String {
+    format!("{:.3}", value)
+}
+
+pub fn get_setting_out(trench_layout: &TrenchLayout) -> Result<Vec<SettingOut>> {
+    trench_layout
+        .trenches
+        .iter()
+        .enumerate()
+        .map(|(i, trench)| SettingOut::new(i + 1, trench))
+        .collect()
+}
+
+pub fn write_setting_out(
+    trench_layout: &TrenchLayout,
+    format: SettingOutFormat,
+    setting_out_path: &Path,
+) -> Result<()> {
+    let setting_out = get_setting_out(trench_layout)?;
+    let mut writer = csv::Writer::from_writer(fs_err::File::create(setting_out_path)?);
+    match format {
+        SettingOutFormat::Trenches => {
+            writer.write_record([
+                "trench_id",
+                "corner_1_x",
+                "corner_1_y",
+                "corner_2_x",
+                "corner_2_y",
+                "corner_3_x",
+                "corner_3_y",
+                "corner_4_x",
+                "corner_4_y",
+                "start_x",
+                "start_y",
+                "end_x",
+                "end_y",
+            ])?;
+            for trench in setting_out.iter() {
+                let (start, end) = trench.centre_line;
+                let mut record = vec![trench.trench_id.to_string()];
+                for coord in trench.corners.iter().chain([start, end].iter()) {
+                    record.push(format_coordinate(coord.x));
+                    record.push(format_coordinate(coord.y));
+                }
+                writer.write_record(record)?;
+            }
+        }
+        SettingOutFormat::SurveyPoints => {
+            writer.write_record(["point", "easting", "northing", "description"])?;
+            for trench in setting_out.iter() {
+                let (start, end) = trench.centre_line;
+                let points = trench
+                    .corners
+                    .iter()
+                    .enumerate()
+                    .map(|(i, corner)| (format!("C{}", i + 1), "corner", *corner))
+                    .chain([
+                        ("S".to_string(), "centre line start", start),
+                        ("E".to_string(), "centre line end", end),
+                    ]);
+                for (suffix, description, coord) in points {
+                    writer.write_record([
+                        format!("T{}-{}", trench.trench_id, suffix),
+                        format_coordinate(coord.x),
+                        format_coordinate(coord.y),
+                        format!("trench {} {}", trench.trench_id, description),
+                    ])?;
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{coord, MultiPolygon, Rect};
+
+    fn layout(trenches: Vec<Polygon>) -> TrenchLayout {
+        TrenchLayout {
+            trenches: MultiPolygon(trenches),
+            metadata: None,
+        }
+    }
+
+    fn trench(min: (f64, f64), max: (f64, f64)) -> Polygon {
+        Rect::new(coord! { x: min.0, y: min.1 }, coord! { x: max.0, y: max.1 }).to_polygon()
+    }
+
+    #[test]
+    fn centre_line_runs_between_the_short_ends() {
+        for trench in [
+            trench((0.0, 0.0), (2.0, 30.0)),
+            trench((0.0, 0.0), (30.0, 2.0)),
+        ] {
+            let setting_out = SettingOut::new(1, &trench).unwrap();
+            let (start, end) = setting_out.centre_line;
+            assert!((distance(start, end) - 30.0).abs() < 1e-9);
+            let corners = setting_out.corners;
+            assert!(corners.iter().all(|corner| {
+                [0.0, 2.0, 30.0].contains(&corner.x) && [0.0, 2.0, 30.0].contains(&corner.y)
+            }));
+        }
+    }
+
+    #[test]
+    fn survey_points_are_named_by_trench() {
+        let layout = layout(vec![
+            trench((0.0, 0.0), (2.0, 30.0)),
+            trench((10.0, 0.0), (12.0, 30.0)),
+        ]);
+        let ids: Vec<usize> = get_setting_out(&layout)
+            .unwrap()
+            .iter()
+            .map(|trench| trench.trench_id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let path = std::env::temp_dir().join(format!("setting_out_{}.csv", std::process::id()));
+        write_setting_out(&layout, SettingOutFormat::SurveyPoints, &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let points: Vec<&str> = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(points.len(), 12);
+        assert_eq!(
+            &points[..6],
+            ["T1-C1", "T1-C2", "T1-C3", "T1-C4", "T1-S", "T1-E"]
+        );
+        assert_eq!(points[6], "T2-C1");
+
+        write_setting_out(&layout, SettingOutFormat::Trenches, &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(2).unwrap().starts_with("2,"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::LayoutMetadata;
+
+// resampling used for the confidence interval of a mean
+#[derive(Debug, Clone, Copy)]
+pub struct Bootstrap {
+    pub resamples: usize,
+    pub confidence: f64, // between 0 and 1
+    pub seed: u64,
+}
+
+// spread of a value, such as detection rate, across the layouts of a config
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub standard_deviation: f64,
+    pub min: f64,
+    pub max: f64,
+    pub percentile_5: f64,
+    pub percentile_25: f64,
+    pub percentile_75: f64,
+    pub percentile_95: f64,
+    pub confidence_interval: (f64, f64), // of the mean
+}
+
+// what the layouts of a config came to on average
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutSummary {
+    pub mean_coverage: f64, // percentage
+    pub mean_trenches: f64,
+    pub mean_clipped_trenches: f64,
+    pub mean_trench_length: f64, // meters
+    pub mean_trench_area: f64,   // square meters
+}
+
+// a row of the statistics CSV, for a single LOE or "all" of them
+#[derive(Debug, Clone, Serialize)]
+pub struct StatisticsRecord {
+    pub run: String,
+    pub site: String,
+    pub loe: String,
+    pub period: Option<String>,
+    pub geology: Option<String>,
+    pub layouts: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub standard_deviation: f64,
+    pub min: f64,
+    pub max: f64,
+    pub percentile_5: f64,
+    pub percentile_25: f64,
+    pub percentile_75: f64,
+    pub percentile_95: f64,
+    pub confidence_low: f64,
+    pub confidence_high: f64,
+    pub mean_coverage: Option<f64>,
+    pub mean_trenches: Option<f64>,
+    pub mean_clipped_trenches: Option<f64>,
+    pub mean_trench_length: Option<f64>,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Bootstrap {
+            resamples: 1000,
+            confidence: 0.95,
+            seed: 0,
+        }
+    }
+}
+
+impl Bootstrap {
+    fn confidence_interval(&self, values: &[f64]) -> (f64, f64) {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut means: Vec<f64> = (0..self.resamples)
+            .map(|_| {
+                let total: f64 = (0..values.len())
+                    .map(|_| values[rng.gen_range(0..values.len())])
+                    .sum();
+                total / values.len() as f64
+            })
+            .collect();
+        means.sort_by(f64::total_cmp);
+        let tail = (1.0 - self.confidence) / 2.0;
+        (percentile(&means, tail), percentile(&means, 1.0 - tail))
+    }
+}
+
+// linear interpolation between the closest ranks, fraction between 0 and 1
+pub fn percentile(sorted_values: &[f64], fraction: f64) -> f64 {
+    let rank = fraction * (sorted_values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted_values[lower] + (sorted_values[upper] - sorted_values[lower]) * (rank - lower as f64)
+}
+
+impl Summary {
+    pub fn new(values: &[f64], bootstrap: &Bootstrap) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted_values = values.to_vec();
+        sorted_values.sort_by(f64::total_cmp);
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        // sample standard deviation, zero for a single layout
+        let variance = if count > 1 {
+            values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64
+        } else {
+            0.0
+        };
+        Some(Summary {
+            count,
+            mean,
+            median: percentile(&sorted_values, 0.5),
+            standard_deviation: variance.sqrt(),
+            min: sorted_values[0],
+            max: sorted_values[count - 1],
+            percentile_5: percentile(&sorted_values, 0.05),
+            percentile_25: percentile(&sorted_values, 0.25),
+            percentile_75: percentile(&sorted_values, 0.75),
+            percentile_95: percentile(&sorted_values, 0.95),
+            confidence_interval: bootstrap.confidence_interval(values),
+        })
+    }
+}
+
+impl LayoutSummary {
+    pub fn new(metadata: &[LayoutMetadata]) -> Option<Self> {
+        if metadata.is_empty() {
+            return None;
+        }
+        let mean = |value: fn(&LayoutMetadata) -> f64| {
+            metadata.iter().map(value).sum::<f64>() / metadata.len() as f64
+        };
+        Some(LayoutSummary {
+            mean_coverage: mean(|metadata| metadata.coverage),
+            mean_trenches: mean(|metadata| metadata.trenches as f64),
+            mean_clipped_trenches: mean(|metadata| metadata.clipped_trenches as f64),
+            mean_trench_length: mean(|metadata| metadata.total_length),
+            mean_trench_area: mean(|metadata| metadata.total_area),
+        })
+    }
+}
+
+impl StatisticsRecord {
+    pub fn new(run: &str, site: &str, loe: &str, summary: &Summary) -> Self {
+        StatisticsRecord {
+            run: run.to_string(),
+            site: site.to_string(),
+            loe: loe.to_string(),
+            period: None,
+            geology: None,
+            layouts: summary.count,
+            mean: summary.mean,
+            median: summary.median,
+            standard_deviation: summary.standard_deviation,
+            min: summary.min,
+            max: summary.max,
+            percentile_5: summary.percentile_5,
+            percentile_25: summary.percentile_25,
+            percentile_75: summary.percentile_75,
+            percentile_95: summary.percentile_95,
+            confidence_low: summary.confidence_interval.0,
+            confidence_high: summary.confidence_interval.1,
+            mean_coverage: None,
+            mean_trenches: None,
+            mean_clipped_trenches: None,
+            mean_trench_length: None,
+        }
+    }
+
+    // the context of the site an LOE is at
+    pub fn with_site(self, period: Option<String>, geology: Option<String>) -> Self {
+        StatisticsRecord {
+            period,
+            geology,
+            ..self
+        }
+    }
+
+    pub fn with_layout_summary(self, layout_summary: Option<LayoutSummary>) -> Self {
+        StatisticsRecord {
+            mean_coverage: layout_summary.map(|summary| summary.mean_coverage),
+            mean_trenches: layout_summary.map(|summary| summary.mean_trenches),
+            mean_clipped_trenches: layout_summary.map(|summary| summary.mean_clipped_trenches),
+            mean_trench_length: layout_summary.map(|summary| summary.mean_trench_length),
+            ..self
+        }
+    }
+}
+
+pub fn write_statistics(records: &[StatisticsRecord], statistics_path: &Path) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(fs_err::File::create(statistics_path)?);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_interpolate_between_ranks() {
+        let values: Vec<f64> = (0..=10).map(|value| value as f64 * 10.0).collect();
+        assert_eq!(percentile(&values, 0.0), 0.0);
+        assert_eq!(percentile(&values, 0.25), 25.0);
+        assert_eq!(percentile(&values, 1.0), 100.0);
+        assert_eq!(percentile(&[1.0, 2.0], 0.5), 1.5);
+
+        let summary = Summary::new(&[4.0, 1.0, 3.0, 2.0], &Bootstrap::default()).unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!((summary.min, summary.max), (1.0, 4.0));
+        assert_eq!((summary.mean, summary.median), (2.5, 2.5));
+        assert!((summary.standard_deviation - (5.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!(Summary::new(&[], &Bootstrap::default()).is_none());
+        // a single layout has no spread
+        let single = Summary::new(&[7.0], &Bootstrap::default()).unwrap();
+        assert_eq!(single.standard_deviation, 0.0);
+        assert_eq!(single.confidence_interval, (7.0, 7.0));
+    }
+
+    #[test]
+    fn bootstrap_interval_is_repeatable_and_holds_the_mean() {
+        let values: Vec<f64> = (0..50).map(|value| (value % 7) as f64 * 10.0).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let bootstrap = Bootstrap {
+            resamples: 500,
+            confidence: 0.9,
+            seed: 3,
+        };
+        let (low, high) = bootstrap.confidence_interval(&values);
+        assert!(low < mean && mean < high);
+        assert_eq!(bootstrap.confidence_interval(&values), (low, high));
+        // a higher confidence gives a wider interval from the same resamples
+        let wider = Bootstrap {
+            confidence: 0.99,
+            ..bootstrap
+        }
+        .confidence_interval(&values);
+        assert!(wider.0 <= low && high <= wider.1);
+    }
+
+    #[test]
+    fn layout_summary_averages_the_metadata() {
+        let metadata = |trenches, clipped_trenches, coverage| LayoutMetadata {
+            rotation: 0.0,
+            spacing: 20.0,
+            x_offset: 0.0,
+            y_offset: 0.0,
+            trench_width: 2.0,
+            coverage,
+            total_area: 60.0 * trenches as f64,
+            total_length: 30.0 * trenches as f64,
+            trenches,
+            clipped_trenches,
+        };
+        let summary = LayoutSummary::new(&[metadata(4, 1, 4.0), metadata(6, 3, 6.0)]).unwrap();
+        assert_eq!(summary.mean_trenches, 5.0);
+        assert_eq!(summary.mean_clipped_trenches, 2.0);
+        assert_eq!(summary.mean_coverage, 5.0);
+        assert_eq!(summary.mean_trench_length, 150.0);
+        assert_eq!(summary.mean_trench_area, 300.0);
+        assert!(LayoutSummary::new(&[]).is_none());
+    }
+}
@@ -1,57 +1,374 @@
 use geo::{
-    coord, Area, BooleanOps, Centroid, EuclideanDistance, LineString, MultiPolygon, Point, Polygon,
-    Rotate, Translate,
+    coord, Area, BooleanOps, Centroid, Contains, Coord, EuclideanDistance, Intersects, LineString,
+    MultiLineString, MultiPolygon, Point, Polygon, Rotate, Translate,
 };
 use rayon::prelude::*;
-use trenching_optimisation::array::{Configuration, PatternRotationAxis};
+use trenching_optimisation::array::Configuration;
 use trenching_optimisation::{
-    Degree, Distribution, Percentage, Rectangle, Structure, TrenchConfig, TrenchLayout,
+    ClippingPolicy, CoverageFailure, CoverageSolver, Degree, Distribution, Excavation,
+    LayoutPlacement, Offset, Percentage, Placement, Rectangle, Structure, TrenchConfig,
+    TrenchLayout,
 };
 
-pub fn create_layouts(
+use crate::centre_line;
+use crate::radial;
+use crate::random;
+
+// trenches are cut to the area left once exclusion zones are taken out of the LOE, spokes and
+// rings laid around their focus whatever the placement
+pub fn create_layouts(config: &TrenchConfig, excavation: &Excavation) -> Option<Vec<TrenchLayout>> {
+    if let Structure::Spokes(_) | Structure::Rings(_) = config.structure {
+        return radial::create_layouts(config, excavation);
+    }
+    if let Placement::Random { seed, layouts } | Placement::StratifiedRandom { seed, layouts } =
+        config.placement
+    {
+        return random::create_layouts(config, excavation, seed, layouts);
+    }
+    if let Placement::CentreLine = config.placement {
+        return centre_line::create_layouts(config, excavation);
+    }
+    // nothing is left when the exclusion zones cover the LOE
+    let centroid = excavation.available.centroid()?;
+    let max_distance_from_centroid =
+        get_max_distance_from_centroid(centroid, &excavation.available);
+
+    match config.distribution {
+        Distribution::Spacing(spacing) => Some(get_layouts_from_spacing(
+            excavation,
+            *config,
+            max_distance_from_centroid,
+            centroid,
+            spacing,
+        )),
+        Distribution::Coverage(coverage) => get_layouts_from_coverage(
+            excavation,
+            *config,
+            max_distance_from_centroid,
+            centroid,
+            coverage,
+        ),
+        Distribution::Cost(_) | Distribution::Count(_) => {
+            create_layouts(&with_equivalent_coverage(config, excavation), excavation)
+        }
+    }
+}
+
+// a budget or number of trenches as the coverage it comes to at this excavation
+pub fn with_equivalent_coverage(config: &TrenchConfig, excavation: &Excavation) -> TrenchConfig {
+    let coverage = match config.distribution {
+        Distribution::Cost(budget) => {
+            config
+                .cost
+                .coverage_of_budget(budget, config.structure, excavation)
+        }
+        Distribution::Count(trenches) => {
+            excavation.coverage_of_trenches(config.structure, trenches)
+        }
+        Distribution::Spacing(_) | Distribution::Coverage(_) => return *config,
+    };
+    TrenchConfig {
+        distribution: Distribution::Coverage(coverage),
+        ..*config
+    }
+}
+
+// one of the layouts from create_layouts, at a chosen rotation and offset,
+// with the spacing used to hit the coverage
+pub fn create_single_layout(
     config: &TrenchConfig,
-    limit_of_excavation: Polygon,
-) -> Option<Vec<TrenchLayout>> {
-    // exclude holes as removed in preprocessing
-    let centroid = limit_of_excavation.centroid().unwrap();
-    let max_distance_from_centroid = get_max_distance_from_centroid(centroid, &limit_of_excavation);
+    excavation: &Excavation,
+    rotation: i32,
+    offset: Offset,
+) -> Result<TrenchLayout, CoverageFailure> {
+    if let Structure::Spokes(_) | Structure::Rings(_) = config.structure {
+        return radial::create_single_layout(config, excavation, offset);
+    }
+    if let Placement::Random { seed, .. } | Placement::StratifiedRandom { seed, .. } =
+        config.placement
+    {
+        return random::create_single_layout(config, excavation, seed, rotation);
+    }
+    if let Placement::CentreLine = config.placement {
+        return centre_line::create_single_layout(config, excavation, offset);
+    }
+    let centroid = excavation
+        .available
+        .centroid()
+        .ok_or(CoverageFailure::NothingAvailable)?;
+    let max_distance_from_centroid =
+        get_max_distance_from_centroid(centroid, &excavation.available);
 
     match config.distribution {
         Distribution::Spacing(spacing) => {
-            return Some(get_layouts_from_spacing(
-                &limit_of_excavation,
-                *config,
-                max_distance_from_centroid,
-                centroid,
-                spacing,
-            ));
+            create_layout(config, excavation, spacing, Degree(rotation as f64), offset)
         }
         Distribution::Coverage(coverage) => {
-            return get_layouts_from_coverage(
-                &limit_of_excavation,
-                *config,
-                max_distance_from_centroid,
-                centroid,
-                coverage,
-            );
+            let estimated_spacing =
+                estimate_spacing(config, &excavation.coverage_of_available(coverage));
+            solve_coverage(
+                config,
+                excavation,
+                max_distance_from_centroid * 4.0,
+                estimated_spacing,
+                coverage.0,
+                rotation,
+                offset,
+                |config, spacing| {
+                    lattice_pattern(
+                        config,
+                        max_distance_from_centroid,
+                        centroid,
+                        spacing,
+                        Degree(rotation as f64),
+                        offset,
+                    )
+                },
+            )
+            .map(solution_layout)
         }
+        Distribution::Cost(_) | Distribution::Count(_) => create_single_layout(
+            &with_equivalent_coverage(config, excavation),
+            excavation,
+            rotation,
+            offset,
+        ),
     }
 }
 
-fn get_size_of_grid(max_distance_from_centroid: &f64, spacing: &f64) -> i32 {
-    (max_distance_from_centroid / spacing).floor() as i32
+// single layout at a fixed spacing, rotation and offset, used when searching for a layout, and
+// none when exclusion zones leave nothing available
+pub fn create_layout(
+    config: &TrenchConfig,
+    excavation: &Excavation,
+    spacing: f64,
+    rotation: Degree,
+    offset: Offset,
+) -> Result<TrenchLayout, CoverageFailure> {
+    if let Structure::Spokes(_) | Structure::Rings(_) = config.structure {
+        return Ok(radial::create_layout(config, excavation, spacing, offset));
+    }
+    let centroid = excavation
+        .available
+        .centroid()
+        .ok_or(CoverageFailure::NothingAvailable)?;
+    let max_distance_from_centroid =
+        get_max_distance_from_centroid(centroid, &excavation.available);
+    let trench_pattern = lattice_pattern(
+        config,
+        max_distance_from_centroid,
+        centroid,
+        spacing,
+        rotation,
+        offset,
+    );
+    Ok(placed_layout(
+        fit_to_excavation(&trench_pattern, excavation, config),
+        excavation,
+        config,
+        LayoutPlacement {
+            rotation,
+            spacing,
+            offset,
+        },
+    ))
+}
+
+// the lattice at a spacing, shifted by the offset and turned around the centroid
+fn lattice_pattern(
+    config: &TrenchConfig,
+    max_distance_from_centroid: f64,
+    centroid: Point,
+    spacing: f64,
+    rotation: Degree,
+    offset: Offset,
+) -> MultiPolygon {
+    get_layout_from_spacing(
+        *config,
+        max_distance_from_centroid,
+        centroid,
+        spacing,
+        offset,
+    )
+    .rotate_around_point(rotation.0, centroid)
+}
+
+// trenches cut to the excavation, with where they were placed and what they cover
+pub fn placed_layout(
+    trenches: MultiPolygon,
+    excavation: &Excavation,
+    config: &TrenchConfig,
+    placement: LayoutPlacement,
+) -> TrenchLayout {
+    let coverage = calculate_coverage(&trenches, excavation);
+    TrenchLayout::new(trenches, &placement, config.structure, coverage)
+}
+
+// trenches at the minimum spacing touch, and the sweep in geo panics on polygons sharing an edge
+// within one operand, so each trench is cut on its own
+fn clip_each<'a>(
+    trench_pattern: &'a MultiPolygon,
+    available: &'a MultiPolygon,
+) -> impl ParallelIterator<Item = Polygon> + 'a {
+    trench_pattern
+        .0
+        .par_iter()
+        .filter(|trench| available.intersects(*trench))
+        .flat_map(|trench| clip(trench, available))
+}
+
+fn clip(trench: &Polygon, available: &MultiPolygon) -> Vec<Polygon> {
+    available
+        .boolean_op(
+            &MultiPolygon(vec![trench.clone()]),
+            geo::OpType::Intersection,
+        )
+        .0
+}
+
+// cut a pattern to the excavation as the config's clipping policy says, coverage is then
+// measured on what is left
+pub fn fit_to_excavation(
+    trench_pattern: &MultiPolygon,
+    excavation: &Excavation,
+    config: &TrenchConfig,
+) -> MultiPolygon {
+    let available = &excavation.available;
+    match config.clipping {
+        ClippingPolicy::Clip => MultiPolygon(clip_each(trench_pattern, available).collect()),
+        ClippingPolicy::WholeOnly => MultiPolygon(
+            trench_pattern
+                .0
+                .par_iter()
+                .filter(|trench| available.contains(*trench))
+                .cloned()
+                .collect(),
+        ),
+        ClippingPolicy::MinimumLength(minimum_length) => {
+            let width = config.structure.get_trench_width();
+            // a trench crossing a hole is cut in two, each judged on its own length
+            MultiPolygon(
+                clip_each(trench_pattern, available)
+                    .filter(|piece| piece.unsigned_area() / width > minimum_length)
+                    .collect(),
+            )
+        }
+        ClippingPolicy::Shorten => MultiPolygon(
+            trench_pattern
+                .0
+                .par_iter()
+                .filter(|trench| available.intersects(*trench))
+                .flat_map(|trench| shorten_to_fit(trench, available))
+                .collect(),
+        ),
+    }
+}
+
+// the stretches of a trench where its full width is inside, each as a trench of its own,
+// found by where its centre line and both long sides are inside, trenches that are not
+// rectangles have no long sides to follow and are clipped
+fn shorten_to_fit(trench: &Polygon, available: &MultiPolygon) -> Vec<Polygon> {
+    let Some((start, across, along)) = rectangle_sides(trench) else {
+        return clip(trench, available);
+    };
+    let length_squared = along.x * along.x + along.y * along.y;
+    if length_squared == 0.0 {
+        return vec![];
+    }
+    let inside = [0.0, 0.5, 1.0]
+        .iter()
+        .map(|fraction| {
+            let side_start = start + across * *fraction;
+            let side = MultiLineString(vec![LineString(vec![side_start, side_start + along])]);
+            available
+                .clip(&side, false)
+                .iter()
+                .filter_map(|piece| {
+                    // position of each end along the trench, from 0 to 1
+                    piece
+                        .coords()
+                        .map(|coord| {
+                            let offset = *coord - side_start;
+                            (offset.x * along.x + offset.y * along.y) / length_squared
+                        })
+                        .fold(None, |range: Option<(f64, f64)>, position| match range {
+                            Some((low, high)) => Some((low.min(position), high.max(position))),
+                            None => Some((position, position)),
+                        })
+                })
+                .collect::<Vec<(f64, f64)>>()
+        })
+        .reduce(|a, b| overlap_ranges(&a, &b))
+        .unwrap_or_default();
+    inside
+        .into_iter()
+        .filter(|(low, high)| high > low)
+        .map(|(low, high)| {
+            let end_start = start + along * low;
+            let end_end = start + along * high;
+            Polygon::new(
+                LineString(vec![
+                    end_start,
+                    end_start + across,
+                    end_end + across,
+                    end_end,
+                    end_start,
+                ]),
+                vec![],
+            )
+        })
+        .collect()
+}
+
+// the first corner and the sides across one end and along a rectangle with its corners ordered
+// as plot_trench orders them, the first two across one end
+fn rectangle_sides(trench: &Polygon) -> Option<(Coord, Coord, Coord)> {
+    let corners = &trench.exterior().0;
+    if corners.len() != 5 || !trench.interiors().is_empty() {
+        return None;
+    }
+    let across = corners[1] - corners[0];
+    let along = corners[3] - corners[0];
+    // rounding moves the corners of a rotated trench a little
+    let (width, length) = (across.x.hypot(across.y), along.x.hypot(along.y));
+    let opposite = corners[2] - (corners[1] + along);
+    let is_rectangle = (across.x * along.x + across.y * along.y).abs() <= 1e-9 * width * length
+        && opposite.x.hypot(opposite.y) <= 1e-9 * (width + length)
+        && corners[4] == corners[0];
+    is_rectangle.then_some((corners[0], across, along))
+}
+
+// the stretches covered by both lists of ranges
+fn overlap_ranges(a: &[(f64, f64)], b: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    a.iter()
+        .flat_map(|(a_low, a_high)| {
+            b.iter().filter_map(move |(b_low, b_high)| {
+                let low = a_low.max(*b_low);
+                let high = a_high.min(*b_high);
+                if high > low {
+                    Some((low, high))
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
 }
 
-fn spacing_smaller_than_minimum(spacing: &f64, minimum_spacing: &f64) -> bool {
-    spacing < minimum_spacing
+fn get_size_of_grid(max_distance_from_centroid: &f64, spacing: &f64) -> i32 {
+    (max_distance_from_centroid / spacing).floor() as i32
 }
 
-fn estimate_spacing(config: &TrenchConfig, coverage: &Percentage) -> f64 {
+pub fn estimate_spacing(config: &TrenchConfig, coverage: &Percentage) -> f64 {
     match config.structure {
-        Structure::Parallel(line) => line.width / (coverage.percentage_as_decimal()),
+        Structure::Parallel(line) | Structure::Rings(line) => {
+            line.width / (coverage.percentage_as_decimal())
+        }
+        Structure::Spokes(line) => 2.0 * line.width / coverage.percentage_as_decimal(),
         Structure::Array(rectangle, array_config) => {
-            let spacing =
-                ((rectangle.width * rectangle.length) / (coverage.percentage_as_decimal())).sqrt();
+            let spacing = ((rectangle.width * rectangle.length)
+                / (coverage.percentage_as_decimal() * array_config.lattice.row_spacing()))
+            .sqrt();
             if array_config.separated {
                 spacing / 2.0
             } else {
@@ -62,8 +379,6 @@ fn estimate_spacing(config: &TrenchConfig, coverage: &Percentage) -> f64 {
 }
 
 fn trench_of_array_coordinate(
-    x_index: usize,
-    y_index: usize,
     x_offset: i32,
     y_offset: i32,
     centroid: Point,
@@ -71,38 +386,18 @@ fn trench_of_array_coordinate(
     array_config: &Configuration,
     rectangle: Rectangle,
 ) -> Option<Polygon> {
-    let trench_centroid = centroid.translate(x_offset as f64 * spacing, y_offset as f64 * spacing);
-    let is_alternate_point = (x_index + y_index) % 2 == 0;
-    let rotation = match array_config.pattern_rotation_axis {
-        PatternRotationAxis::ByCell => {
-            if is_alternate_point {
-                array_config.base_angle
-            } else {
-                array_config.alternate_angle
-            }
-        }
-        PatternRotationAxis::ByColumn => {
-            if x_index % 2 == 0 {
-                array_config.base_angle
-            } else {
-                array_config.alternate_angle
-            }
-        }
-    };
-
-    if array_config.separated & is_alternate_point {
-        None
-    } else {
-        Some(plot_trench(
-            trench_centroid,
-            rectangle.width,
-            rectangle.length,
-            rotation,
-        ))
-    }
+    let (x, y) = array_config.lattice.position(x_offset, y_offset);
+    let trench_centroid = centroid.translate(x * spacing, y * spacing);
+    let rotation = array_config.angle_of_cell(x_offset, y_offset)?;
+    Some(plot_trench(
+        trench_centroid,
+        rectangle.width,
+        rectangle.length,
+        rotation,
+    ))
 }
 
-fn plot_trench(centroid: Point, width: f64, length: f64, rotation: Degree) -> Polygon<f64> {
+pub fn plot_trench(centroid: Point, width: f64, length: f64, rotation: Degree) -> Polygon<f64> {
     let trench_exterior = vec![
         coord! { x: centroid.x() - width / 2.0, y: centroid.y() - length / 2.0 },
         coord! { x: centroid.x() + width / 2.0, y: centroid.y() - length / 2.0 },
@@ -114,61 +409,187 @@ fn plot_trench(centroid: Point, width: f64, length: f64, rotation: Degree) -> Po
 }
 
 fn get_layouts_from_coverage(
-    limit_of_excavation: &Polygon,
+    excavation: &Excavation,
     config: TrenchConfig,
     max_distance_from_centroid: f64,
     centroid: Point,
     coverage: Percentage,
 ) -> Option<Vec<TrenchLayout>> {
-    let estimated_spacing = estimate_spacing(&config, &coverage);
-    if spacing_smaller_than_minimum(&estimated_spacing, &config.minimum_spacing) {
-        return None;
-    }
-    let trenches = get_layout_from_spacing(
-        config,
-        max_distance_from_centroid,
-        centroid,
-        estimated_spacing,
-    );
-    // in parallel iterate over all
-    let limit_of_excavation = MultiPolygon(vec![limit_of_excavation.clone()]);
-
-    let trench_patterns: Vec<TrenchLayout> = (0..config.structure.get_rotational_symmetry())
-        // let trench_patterns: Vec<TrenchLayout> = (171..172)
-        .into_par_iter()
-        .filter_map(|rotation| {
-            adjust_trench_layout_to_coverage(
-                &trenches,
-                coverage.0,
-                centroid,
-                &limit_of_excavation,
-                estimated_spacing,
-                &config,
-                rotation,
-                &max_distance_from_centroid,
-            )
+    let estimated_spacing = estimate_spacing(&config, &excavation.coverage_of_available(coverage));
+
+    // every rotation and offset gives a layout or the reason it could not
+    let rotations = config.get_rotations(excavation);
+    let results: Vec<(i32, Offset, Result<CoverageSolution, CoverageFailure>)> = config
+        .get_offsets()
+        .into_iter()
+        .flat_map(|offset| {
+            rotations
+                .par_iter()
+                .map(|&rotation| {
+                    let result = solve_coverage(
+                        &config,
+                        excavation,
+                        max_distance_from_centroid * 4.0,
+                        estimated_spacing,
+                        coverage.0,
+                        rotation,
+                        offset,
+                        |config, spacing| {
+                            lattice_pattern(
+                                config,
+                                max_distance_from_centroid,
+                                centroid,
+                                spacing,
+                                Degree(rotation as f64),
+                                offset,
+                            )
+                        },
+                    );
+                    (rotation, offset, result)
+                })
+                .collect::<Vec<_>>()
         })
         .collect();
+    collect_solutions(&config, coverage, results)
+}
+
+// the layouts hitting the coverage, saying why the others could not
+pub fn collect_solutions(
+    config: &TrenchConfig,
+    coverage: Percentage,
+    results: Vec<(i32, Offset, Result<CoverageSolution, CoverageFailure>)>,
+) -> Option<Vec<TrenchLayout>> {
+    let tested = results.len();
+    let mut trench_patterns = Vec::new();
+    let mut achieved_coverages = Vec::new();
+    let mut shortened = 0;
+    let mut failures = Vec::new();
+    for (rotation, offset, result) in results {
+        match result {
+            Ok(solution) => {
+                achieved_coverages.push(solution.coverage);
+                if solution.length.is_some() {
+                    shortened += 1;
+                }
+                trench_patterns.push(solution.layout);
+            }
+            Err(failure) => failures.push((
+                format!(
+                    "rotation {} and offset ({:.2}, {:.2})",
+                    rotation, offset.x, offset.y
+                ),
+                failure,
+            )),
+        }
+    }
+    report_failures(&config.solver, tested, &failures);
     if trench_patterns.is_empty() {
         return None;
     }
+    println!(
+        "Coverage of {:.2}% within {} for {} of {} layouts, {} with shortened trenches, achieved {:.2}% to {:.2}%",
+        coverage.0,
+        config.solver.tolerance,
+        trench_patterns.len(),
+        tested,
+        shortened,
+        achieved_coverages.iter().cloned().fold(f64::INFINITY, f64::min),
+        achieved_coverages.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+    );
     Some(trench_patterns)
 }
 
+// how many layouts missed the target for each reason and the coverages they reached, as a sweep
+// of every rotation and offset can miss it hundreds of times
+pub fn report_failures(
+    solver: &CoverageSolver,
+    tested: usize,
+    failures: &[(String, CoverageFailure)],
+) {
+    if solver.list_failures {
+        for (layout, failure) in failures.iter() {
+            println!("No layout at {}: {}", layout, failure);
+        }
+    }
+    for counted in count_failures(failures) {
+        match counted.coverages {
+            Some((min, max)) => println!(
+                "No layout for {} of {}: {} {:.2}% to {:.2}%",
+                counted.count, tested, counted.reason, min, max
+            ),
+            None => println!(
+                "No layout for {} of {}: {}",
+                counted.count, tested, counted.reason
+            ),
+        }
+    }
+}
+
+// layouts that missed the target for one reason, and the range of coverage they reached
+#[derive(Debug, PartialEq)]
+struct FailureCount {
+    reason: &'static str,
+    count: usize,
+    coverages: Option<(f64, f64)>,
+}
+
+// each reason in the order it first failed
+fn count_failures(failures: &[(String, CoverageFailure)]) -> Vec<FailureCount> {
+    let mut counts: Vec<FailureCount> = Vec::new();
+    for (_, failure) in failures.iter() {
+        let coverage = failure.coverage();
+        match counts
+            .iter_mut()
+            .find(|counted| counted.reason == failure.reason())
+        {
+            Some(counted) => {
+                counted.count += 1;
+                counted.coverages = match (counted.coverages, coverage) {
+                    (Some((min, max)), Some(coverage)) => {
+                        Some((min.min(coverage), max.max(coverage)))
+                    }
+                    (coverages, _) => coverages,
+                };
+            }
+            None => counts.push(FailureCount {
+                reason: failure.reason(),
+                count: 1,
+                coverages: coverage.map(|coverage| (coverage, coverage)),
+            }),
+        }
+    }
+    counts
+}
+
 fn get_layouts_from_spacing(
-    limit_of_excavation: &Polygon,
+    excavation: &Excavation,
     config: TrenchConfig,
     max_distance_from_centroid: f64,
     centroid: Point,
     spacing: f64,
 ) -> Vec<TrenchLayout> {
-    let trenches = get_layout_from_spacing(config, max_distance_from_centroid, centroid, spacing);
-    get_rotated_trench_patterns(
-        trenches,
-        config.structure.get_rotational_symmetry(),
-        centroid,
-        limit_of_excavation,
-    )
+    config
+        .get_offsets()
+        .into_iter()
+        .flat_map(|offset| {
+            let trenches = get_layout_from_spacing(
+                config,
+                max_distance_from_centroid,
+                centroid,
+                spacing,
+                offset,
+            );
+            get_rotated_trench_patterns(
+                trenches,
+                &config.get_rotations(excavation),
+                centroid,
+                excavation,
+                &config,
+                spacing,
+                offset,
+            )
+        })
+        .collect()
 }
 
 fn get_layout_from_spacing(
@@ -176,19 +597,24 @@ fn get_layout_from_spacing(
     max_distance_from_centroid: f64,
     centroid: Point,
     spacing: f64,
+    offset: Offset,
 ) -> MultiPolygon {
-    let n = get_size_of_grid(&max_distance_from_centroid, &spacing);
+    // shift the pattern origin within a spacing cell, extending the grid to still cover the LOE
+    let origin = centroid.translate(offset.x * spacing, offset.y * spacing);
+    let max_distance_from_origin =
+        max_distance_from_centroid + centroid.euclidean_distance(&origin);
+    let n = get_size_of_grid(&max_distance_from_origin, &spacing);
     let x_offsets = -n..n + 1;
     let trenches = match config.structure {
         Structure::Parallel(line) => {
             x_offsets
                 .into_par_iter()
                 .map(|x_offset| {
-                    let trench_centroid = centroid.translate(x_offset as f64 * spacing, 0.0);
+                    let trench_centroid = origin.translate(x_offset as f64 * spacing, 0.0);
                     plot_trench(
                         trench_centroid,
                         line.width,
-                        max_distance_from_centroid * 2.0,
+                        max_distance_from_origin * 2.0,
                         Degree(0.0),
                     )
                 })
@@ -196,22 +622,27 @@ fn get_layout_from_spacing(
             // TODO: test performance of this vs .push() to Vec
         }
         Structure::Array(rectangle, array_config) => {
-            let y_offsets = -n..n + 1;
+            // rows closer than the spacing need more of them, and shifted rows one more column
+            let n_rows = get_size_of_grid(
+                &max_distance_from_origin,
+                &(spacing * array_config.lattice.row_spacing()),
+            );
+            let y_offsets = -n_rows..n_rows + 1;
+            let x_offsets = if array_config.lattice.row_offset() > 0.0 {
+                -n - 1..n + 1
+            } else {
+                x_offsets
+            };
             x_offsets
                 .into_par_iter()
-                .enumerate()
-                .flat_map(|(x_index, x_offset)| {
+                .flat_map(|x_offset| {
                     y_offsets
                         .clone()
-                        .into_iter()
-                        .enumerate()
-                        .filter_map(move |(y_index, y_offset)| {
+                        .filter_map(move |y_offset| {
                             trench_of_array_coordinate(
-                                x_index,
-                                y_index,
                                 x_offset,
                                 y_offset,
-                                centroid,
+                                origin,
                                 spacing,
                                 &array_config,
                                 rectangle,
@@ -221,127 +652,517 @@ fn get_layout_from_spacing(
                 })
                 .collect::<Vec<Polygon>>()
         }
+        // laid around their focus by radial before reaching a lattice
+        Structure::Spokes(_) | Structure::Rings(_) => Vec::new(),
     };
     MultiPolygon(trenches)
 }
 
-fn get_max_distance_from_centroid(centroid: Point, limit_of_excavation: &Polygon) -> f64 {
-    let max_distance_from_centroid =
-        limit_of_excavation
-            .exterior()
-            .points()
-            .fold(0.0, |max_distance_from_centroid, p| {
-                let distance = centroid.euclidean_distance(&p);
-                if distance > max_distance_from_centroid {
-                    distance
-                } else {
-                    max_distance_from_centroid
-                }
-            });
+// holes are inside the exteriors, so only the exteriors bound the pattern
+pub fn get_max_distance_from_centroid(centroid: Point, limit_of_excavation: &MultiPolygon) -> f64 {
+    let max_distance_from_centroid = limit_of_excavation
+        .iter()
+        .flat_map(|polygon| polygon.exterior().points())
+        .fold(0.0, |max_distance_from_centroid, p| {
+            let distance = centroid.euclidean_distance(&p);
+            if distance > max_distance_from_centroid {
+                distance
+            } else {
+                max_distance_from_centroid
+            }
+        });
     max_distance_from_centroid
 }
 
-fn calculate_coverage(
-    trench_layout: &MultiPolygon<f64>,
-    limit_of_excavation: &MultiPolygon<f64>,
-) -> f64 {
-    trench_layout.unsigned_area() / limit_of_excavation.unsigned_area() * 100.0
+pub fn calculate_coverage(trench_layout: &MultiPolygon<f64>, excavation: &Excavation) -> f64 {
+    trench_layout.unsigned_area() / excavation.coverage_area * 100.0
 }
 
 fn get_rotated_trench_patterns(
     trenches: MultiPolygon,
-    rotations: i32,
+    rotations: &[i32],
     centroid: Point,
-    limit_of_excavation: &Polygon,
+    excavation: &Excavation,
+    config: &TrenchConfig,
+    spacing: f64,
+    offset: Offset,
 ) -> Vec<TrenchLayout> {
-    let limit_of_excavation_as_multi = MultiPolygon(vec![limit_of_excavation.clone()]);
-
-    let trench_patterns = (0..rotations)
-        .into_par_iter()
-        .map(|rotation| {
+    rotations
+        .par_iter()
+        .map(|&rotation| {
             let trench_pattern = trenches.rotate_around_point(rotation as f64, centroid);
 
             // cut trench to site outline
-            let intersection =
-                limit_of_excavation_as_multi.boolean_op(&trench_pattern, geo::OpType::Intersection);
-
-            let _percentage_coverage =
-                calculate_coverage(&intersection, &limit_of_excavation_as_multi);
-
-            TrenchLayout(intersection)
+            placed_layout(
+                fit_to_excavation(&trench_pattern, excavation, config),
+                excavation,
+                config,
+                LayoutPlacement {
+                    rotation: Degree(rotation as f64),
+                    spacing,
+                    offset,
+                },
+            )
         })
-        .collect();
-    trench_patterns
-    // TODO: return average percentage coverage
+        .collect()
 }
 
-fn check_coverage(current_coverage: f64, target_coverage: f64) -> bool {
-    if current_coverage > target_coverage - 0.05 && current_coverage < target_coverage + 0.05 {
-        true
-    } else {
-        false
+// a layout with the target coverage, and what it actually covers
+pub struct CoverageSolution {
+    pub layout: TrenchLayout,
+    pub length: Option<f64>, // when array trenches were shortened to hit the target
+    pub coverage: f64,
+}
+
+// the layout of a single solution, saying when its trenches were shortened
+pub fn solution_layout(solution: CoverageSolution) -> TrenchLayout {
+    if let Some(length) = solution.length {
+        println!("Trenches shortened to {:.2}m to hit the coverage", length);
     }
+    solution.layout
 }
 
-fn adjust_trench_layout_to_coverage(
-    trench_pattern: &MultiPolygon,
+// a layout tried by the solver, at a spacing or trench length
+struct Trial {
+    value: f64,
+    layout: TrenchLayout,
+    coverage: f64,
+}
+
+// search between a value covering too little and one covering too much, alternating secant and
+// bisection steps so the bracket at least halves every other step, Err holds the closest tried
+#[allow(clippy::result_large_err)]
+fn solve_bracket(
+    mut under: Trial,
+    mut over: Trial,
     target_coverage: f64,
-    centroid: Point,
-    limit_of_excavation: &MultiPolygon,
-    estimated_spacing: f64,
+    solver: &CoverageSolver,
+    try_value: impl Fn(f64) -> Trial,
+) -> Result<Trial, (Trial, Trial)> {
+    for iteration in 0..solver.max_iterations {
+        let value = if iteration % 2 == 0 {
+            under.value
+                + (target_coverage - under.coverage) * (over.value - under.value)
+                    / (over.coverage - under.coverage)
+        } else {
+            (under.value + over.value) / 2.0
+        };
+        // coverage jumps over the target between two values too close to tell apart
+        if (value - under.value).abs() < 1e-9 || (value - over.value).abs() < 1e-9 {
+            break;
+        }
+        let trial = try_value(value);
+        if (trial.coverage - target_coverage).abs() <= solver.tolerance {
+            return Ok(trial);
+        }
+        if trial.coverage < target_coverage {
+            under = trial;
+        } else {
+            over = trial;
+        }
+    }
+    Err((under, over))
+}
+
+// the spacing, or failing that the length of array trenches, giving the pattern the target
+// coverage, at most max_spacing beyond which it is a single trench or none
+#[allow(clippy::too_many_arguments)]
+pub fn solve_coverage(
     config: &TrenchConfig,
+    excavation: &Excavation,
+    max_spacing: f64,
+    estimated_spacing: f64,
+    target_coverage: f64,
     rotation: i32,
-    max_distance_from_centroid: &f64,
-) -> Option<TrenchLayout> {
-    let mut iteration = 0;
-    let mut current_spacing = estimated_spacing.clone();
-    let mut current_trench_pattern = trench_pattern.rotate_around_point(rotation as f64, centroid);
-    let mut intersection =
-        limit_of_excavation.boolean_op(&current_trench_pattern, geo::OpType::Intersection);
-    let mut current_coverage = calculate_coverage(&intersection, limit_of_excavation);
-
-    // println!("Target coverage: {}", target_coverage);
-
-    while (iteration <= 10) && !check_coverage(current_coverage, target_coverage) {
-        // println!("\nCurrent iteration: {}", iteration);
-        // println!("Current spacing: {}", current_spacing);
-        // println!("Current coverage: {}", current_coverage);
-
-        // adjust current_spacing to get closer to target_coverage
-        let adjustment_factor = 0.82_f64.powf(iteration as f64);
-        let error = (target_coverage - current_coverage) / target_coverage;
-        if error < 0.0 {
-            current_spacing = current_spacing * (1.0 + -error * adjustment_factor);
-        } else {
-            current_spacing = current_spacing / (1.0 + error * adjustment_factor);
+    offset: Offset,
+    pattern: impl Fn(&TrenchConfig, f64) -> MultiPolygon,
+) -> Result<CoverageSolution, CoverageFailure> {
+    let solver = &config.solver;
+    let try_layout = |config: &TrenchConfig, spacing: f64, value: f64| {
+        let trench_pattern = pattern(config, spacing);
+        let layout = placed_layout(
+            fit_to_excavation(&trench_pattern, excavation, config),
+            excavation,
+            config,
+            LayoutPlacement {
+                rotation: Degree(rotation as f64),
+                spacing,
+                offset,
+            },
+        );
+        let coverage = layout.metadata.map_or(0.0, |metadata| metadata.coverage);
+        Trial {
+            value,
+            layout,
+            coverage,
         }
+    };
+    let try_spacing = |spacing: f64| try_layout(config, spacing, spacing);
+    let is_within = |trial: &Trial| (trial.coverage - target_coverage).abs() <= solver.tolerance;
+    let solution = |trial: Trial, length: Option<f64>| {
+        Ok(CoverageSolution {
+            layout: trial.layout,
+            length,
+            coverage: trial.coverage,
+        })
+    };
 
-        // check spacing is not too small
-        if spacing_smaller_than_minimum(&current_spacing, &config.minimum_spacing) {
-            // println!("Spacing too small");
-            return None;
+    let first = try_spacing(estimated_spacing.max(config.minimum_spacing));
+    if is_within(&first) {
+        return solution(first, None);
+    }
+    // widen or narrow the spacing until the target lies between two layouts
+    let (under, over) = if first.coverage > target_coverage {
+        let mut over = first;
+        loop {
+            if over.value > max_spacing {
+                return Err(CoverageFailure::SingleTrenchExceeds {
+                    minimum_coverage: over.coverage,
+                });
+            }
+            let trial = try_spacing(over.value * 2.0);
+            if is_within(&trial) {
+                return solution(trial, None);
+            }
+            if trial.coverage < target_coverage {
+                break (trial, over);
+            }
+            over = trial;
+        }
+    } else {
+        let mut under = first;
+        loop {
+            if under.value <= config.minimum_spacing {
+                return Err(CoverageFailure::Unattainable {
+                    maximum_coverage: under.coverage,
+                });
+            }
+            let trial = try_spacing((under.value / 2.0).max(config.minimum_spacing));
+            if is_within(&trial) {
+                return solution(trial, None);
+            }
+            if trial.coverage > target_coverage {
+                break (under, trial);
+            }
+            under = trial;
+        }
+    };
+    let (under, over) = match solve_bracket(under, over, target_coverage, solver, try_spacing) {
+        Ok(trial) => {
+            return solution(trial, None);
         }
+        Err(closest) => closest,
+    };
 
-        current_trench_pattern = get_layout_from_spacing(
-            *config,
-            *max_distance_from_centroid,
-            centroid,
-            current_spacing,
+    // coverage rises smoothly with the length of array trenches at a fixed spacing
+    if let (true, Structure::Array(rectangle, array_config)) =
+        (solver.adjust_length, config.structure)
+    {
+        let spacing = over.value;
+        let try_length = |length: f64| {
+            let shortened = TrenchConfig {
+                structure: Structure::Array(Rectangle::new(rectangle.width, length), array_config),
+                ..*config
+            };
+            try_layout(&shortened, spacing, length)
+        };
+        let no_length = Trial {
+            value: 0.0,
+            layout: TrenchLayout {
+                trenches: MultiPolygon(vec![]),
+                metadata: None,
+            },
+            coverage: 0.0,
+        };
+        let full_length = Trial {
+            value: rectangle.length,
+            ..over
+        };
+        return match solve_bracket(no_length, full_length, target_coverage, solver, try_length) {
+            Ok(trial) => {
+                let length = trial.value;
+                solution(trial, Some(length))
+            }
+            Err((under, over)) => Err(CoverageFailure::NotConverged {
+                closest_coverage: closest_coverage(&under, &over, target_coverage),
+            }),
+        };
+    }
+    Err(CoverageFailure::NotConverged {
+        closest_coverage: closest_coverage(&under, &over, target_coverage),
+    })
+}
+
+fn closest_coverage(under: &Trial, over: &Trial, target_coverage: f64) -> f64 {
+    if target_coverage - under.coverage < over.coverage - target_coverage {
+        under.coverage
+    } else {
+        over.coverage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trial(value: f64, coverage: f64) -> Trial {
+        Trial {
+            value,
+            layout: TrenchLayout {
+                trenches: MultiPolygon(vec![]),
+                metadata: None,
+            },
+            coverage,
+        }
+    }
+
+    fn square(size: f64) -> MultiPolygon {
+        MultiPolygon(vec![Polygon::new(
+            LineString::from(vec![
+                (0.0, 0.0),
+                (size, 0.0),
+                (size, size),
+                (0.0, size),
+                (0.0, 0.0),
+            ]),
+            vec![],
+        )])
+    }
+
+    fn excavation(size: f64) -> Excavation {
+        Excavation::new(
+            &square(size),
+            &MultiPolygon(vec![]),
+            0.0,
+            trenching_optimisation::CoverageBasis::Available,
         )
-        .rotate_around_point(rotation as f64, centroid);
+    }
 
-        intersection =
-            limit_of_excavation.boolean_op(&current_trench_pattern, geo::OpType::Intersection);
+    // 2x30m standard grid trenches 60m apart
+    fn grid() -> TrenchConfig {
+        TrenchConfig::standard_grid(
+            2.0,
+            30.0,
+            trenching_optimisation::array::Lattice::Square,
+            Distribution::Spacing(60.0),
+        )
+        .unwrap()
+    }
 
-        current_coverage = calculate_coverage(&intersection, limit_of_excavation);
-        iteration += 1;
+    #[test]
+    fn solve_bracket_converges_on_smooth_coverage() {
+        let solver = CoverageSolver::default();
+        let solved = solve_bracket(trial(1.0, 2.0), trial(4.0, 8.0), 5.0, &solver, |value| {
+            trial(value, value * 2.0)
+        });
+        let solved = solved.ok().expect("a smooth coverage is hit");
+        assert!((solved.coverage - 5.0).abs() <= solver.tolerance);
+        assert!((solved.value - 2.5).abs() < 0.05);
     }
 
-    if check_coverage(current_coverage, target_coverage) {
-        // println!("Target coverage hit");
-        Some(TrenchLayout(intersection))
-    } else {
-        // println!("Target coverage not hit");
-        None
+    #[test]
+    fn solve_bracket_fails_when_coverage_jumps_over_the_target() {
+        let solver = CoverageSolver::default();
+        let step = |value: f64| trial(value, if value < 2.0 { 0.0 } else { 10.0 });
+        let Err((under, over)) = solve_bracket(step(1.0), step(4.0), 5.0, &solver, step) else {
+            panic!("no value hits a coverage the step jumps over");
+        };
+        assert!(under.value < 2.0 && over.value >= 2.0);
+        assert!(over.value - under.value < 1e-6);
+    }
+
+    #[test]
+    fn shorten_to_fit_cuts_a_rectangle_back_to_its_full_width() {
+        let available = square(10.0);
+        // 2m wide and running from y = 7 to 17, so 3m of it is inside
+        let trench = plot_trench(Point::new(5.0, 12.0), 2.0, 10.0, Degree(0.0));
+        let pieces = shorten_to_fit(&trench, &available);
+        assert_eq!(pieces.len(), 1);
+        assert!((pieces[0].unsigned_area() - 6.0).abs() < 1e-9);
+        assert!(available.contains(&pieces[0]));
+        assert!(rectangle_sides(&pieces[0]).is_some());
+    }
+
+    #[test]
+    fn shorten_to_fit_clips_a_trench_that_is_not_a_rectangle() {
+        let available = square(10.0);
+        let triangle = Polygon::new(
+            LineString::from(vec![(5.0, 5.0), (15.0, 5.0), (5.0, 15.0), (5.0, 5.0)]),
+            vec![],
+        );
+        let pieces = shorten_to_fit(&triangle, &available);
+        let clipped: f64 = clip(&triangle, &available)
+            .iter()
+            .map(|piece| piece.unsigned_area())
+            .sum();
+        let shortened: f64 = pieces.iter().map(|piece| piece.unsigned_area()).sum();
+        assert!((shortened - clipped).abs() < 1e-9);
+        assert!((shortened - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn create_layout_fails_when_exclusions_cover_the_loe() {
+        let config = TrenchConfig::continuous(2.0, Distribution::Spacing(20.0)).unwrap();
+        let excavation = Excavation::new(
+            &square(100.0),
+            &square(100.0),
+            0.0,
+            trenching_optimisation::CoverageBasis::Available,
+        );
+        let layout = create_layout(
+            &config,
+            &excavation,
+            20.0,
+            Degree(0.0),
+            Offset::new(0.0, 0.0),
+        );
+        assert!(matches!(layout, Err(CoverageFailure::NothingAvailable)));
+    }
+
+    #[test]
+    fn failures_are_counted_by_reason_with_the_coverages_reached() {
+        let failures = [
+            CoverageFailure::NotConverged {
+                closest_coverage: 4.9,
+            },
+            CoverageFailure::NothingAvailable,
+            CoverageFailure::NotConverged {
+                closest_coverage: 5.1,
+            },
+            CoverageFailure::NotConverged {
+                closest_coverage: 5.0,
+            },
+        ]
+        .map(|failure| (String::new(), failure));
+        let counts = count_failures(&failures);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(
+            counts[0],
+            FailureCount {
+                reason: failures[0].1.reason(),
+                count: 3,
+                coverages: Some((4.9, 5.1)),
+            }
+        );
+        assert_eq!(
+            counts[1],
+            FailureCount {
+                reason: failures[1].1.reason(),
+                count: 1,
+                coverages: None,
+            }
+        );
+    }
+
+    #[test]
+    fn a_layout_is_made_at_every_rotation_and_offset() {
+        let config = grid().with_offset_steps(2);
+        let excavation = excavation(200.0);
+        let layouts = create_layouts(&config, &excavation).unwrap();
+        assert_eq!(layouts.len(), config.get_rotations(&excavation).len() * 4);
+    }
+
+    #[test]
+    fn shifting_a_pattern_by_its_repeat_leaves_it_unchanged() {
+        let config = grid();
+        let excavation = excavation(200.0);
+        let (x_period, y_period) = config.structure.get_translational_symmetry();
+        let coverage = |offset| {
+            create_layout(&config, &excavation, 60.0, Degree(30.0), offset)
+                .unwrap()
+                .metadata
+                .unwrap()
+                .coverage
+        };
+        let unshifted = coverage(Offset::new(0.0, 0.0));
+        assert!((coverage(Offset::new(x_period, 0.0)) - unshifted).abs() < 1e-9);
+        assert!((coverage(Offset::new(0.0, y_period)) - unshifted).abs() < 1e-9);
+        assert!((coverage(Offset::new(0.25, 0.5)) - unshifted).abs() > 1e-9);
+    }
+
+    fn rectangle(min: (f64, f64), max: (f64, f64)) -> LineString {
+        LineString::from(vec![
+            (min.0, min.1),
+            (max.0, min.1),
+            (max.0, max.1),
+            (min.0, max.1),
+            (min.0, min.1),
+        ])
+    }
+
+    #[test]
+    fn coverage_of_a_holed_loe_with_an_island_leaves_out_the_hole() {
+        let loe = MultiPolygon(vec![
+            Polygon::new(
+                rectangle((0.0, 0.0), (200.0, 200.0)),
+                vec![rectangle((71.3, 73.7), (131.3, 133.7))],
+            ),
+            Polygon::new(rectangle((250.5, 20.5), (290.5, 60.5)), vec![]),
+        ]);
+        let excavation = Excavation::new(
+            &loe,
+            &MultiPolygon(vec![]),
+            0.0,
+            trenching_optimisation::CoverageBasis::Available,
+        );
+        assert!(
+            (excavation.coverage_area - (200.0 * 200.0 - 60.0 * 60.0 + 40.0 * 40.0)).abs() < 1e-6
+        );
+
+        let config = TrenchConfig::continuous(2.0, Distribution::Spacing(10.0)).unwrap();
+        let layout = create_layout(
+            &config,
+            &excavation,
+            10.0,
+            Degree(0.0),
+            Offset::new(0.0, 0.0),
+        )
+        .unwrap();
+        let hole_centre = Point::new(101.3, 103.7);
+        assert!(!layout
+            .trenches
+            .iter()
+            .any(|trench| trench.contains(&hole_centre)));
+        assert!(layout
+            .trenches
+            .iter()
+            .any(|trench| trench.centroid().unwrap().x() > 250.0));
+        let coverage = layout.metadata.unwrap().coverage;
+        assert!((coverage - 20.0).abs() < 1.0);
+        assert!(
+            (coverage - layout.trenches.unsigned_area() / excavation.coverage_area * 100.0).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn aligned_trenches_run_along_the_loe() {
+        let loe = MultiPolygon(vec![Polygon::new(
+            rectangle((0.0, 0.0), (200.0, 40.0)),
+            vec![],
+        )]);
+        let excavation = Excavation::new(
+            &loe,
+            &MultiPolygon(vec![]),
+            0.0,
+            trenching_optimisation::CoverageBasis::Available,
+        );
+        let config = TrenchConfig::continuous(2.0, Distribution::Spacing(10.0))
+            .unwrap()
+            .with_orientation(trenching_optimisation::Orientation::Aligned);
+        let rotation = config.get_fixed_rotation(&excavation).unwrap();
+        let layout = create_layout(
+            &config,
+            &excavation,
+            10.0,
+            Degree(rotation as f64),
+            Offset::new(0.0, 0.0),
+        )
+        .unwrap();
+        assert!(!layout.trenches.0.is_empty());
+        assert!(layout.trenches.iter().all(|trench| {
+            let bounds = geo::BoundingRect::bounding_rect(trench).unwrap();
+            bounds.width() > 10.0 * bounds.height()
+        }));
     }
 }
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use fs_err::File;
-use geo::{coord, Coord, Intersects, LineString, Polygon};
+use geo::{Intersects, MultiPolygon};
 use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
 use std::io::{BufReader, BufWriter};
 
@@ -9,24 +9,23 @@ fn main() -> Result<()> {
     let loe_gj = read_raw_geojson(format!("LOE_{}", &args[1]))?;
 
     let mut loes: Vec<Feature> = Vec::new();
-    let mut loes_polygon: Vec<Polygon> = Vec::new();
+    let mut loes_polygon: Vec<MultiPolygon> = Vec::new();
 
     match loe_gj {
         GeoJson::FeatureCollection(ref collection) => {
             for feature in &collection.features {
-                loes.push(feature.clone());
                 if let Some(ref geom) = feature.geometry {
-                    match geom.value {
-                        Value::Polygon(ref polygon) => {
-                            let poly_exterior = polygon[0]
-                                .iter()
-                                .map(|c| {
-                                    coord! { x: c[0], y: c[1] }
-                                })
-                                .collect::<Vec<Coord>>();
-                            loes_polygon.push(Polygon::new(LineString(poly_exterior), vec![]));
+                    // LOEs keep their islands and holes, such as retained hedgerows and ponds
+                    match geo::Geometry::<f64>::try_from(geom.clone()) {
+                        Ok(geo::Geometry::Polygon(polygon)) => {
+                            loes.push(feature.clone());
+                            loes_polygon.push(MultiPolygon(vec![polygon]));
                         }
-                        _ => println!("Non Polygon GeoJSON not supported"),
+                        Ok(geo::Geometry::MultiPolygon(multi_polygon)) => {
+                            loes.push(feature.clone());
+                            loes_polygon.push(multi_polygon);
+                        }
+                        _ => println!("Non Polygon or MultiPolygon GeoJSON not supported"),
                     }
                 }
             }
@@ -42,7 +41,7 @@ fn main() -> Result<()> {
         for feature in collection.features.iter() {
             if let Some(ref geom) = feature.geometry {
                 for (i, loe_polygon) in loes_polygon.iter().enumerate() {
-                    if compare_loe_to_feature(loe_polygon.clone(), geom) {
+                    if compare_loe_to_feature(loe_polygon, geom) {
                         let mut feature = feature.clone();
                         // standardise the Layer names
                         if feature.contains_property("LANDSCAPE") {
@@ -100,7 +99,7 @@ fn save_feature_collection_to_geojson(
     let writer = BufWriter::new(file);
     let feature_collection = FeatureCollection {
         bbox: None,
-        features: features,
+        features,
         foreign_members: None,
     };
     serde_json::to_writer(writer, &GeoJson::FeatureCollection(feature_collection))?;
@@ -128,25 +127,22 @@ fn read_raw_geojson(file_name: String) -> Result<GeoJson> {
     Ok(gj)
 }
 
-fn compare_loe_to_feature(loe_polygon: Polygon, geom: &Geometry) -> bool {
+fn compare_loe_to_feature(loe_polygon: &MultiPolygon, geom: &Geometry) -> bool {
     match geom.value {
-        Value::Polygon(ref polygon) => {
-            let poly_exterior = polygon[0]
-                .iter()
-                .map(|c| {
-                    coord! { x: c[0], y: c[1] }
-                })
-                .collect::<Vec<Coord>>();
-            let poly = Polygon::new(LineString(poly_exterior), vec![]);
-            if poly.intersects(&loe_polygon) {
-                return true;
-            } else {
-                return false;
+        Value::Polygon(_)
+        | Value::MultiPolygon(_)
+        | Value::LineString(_)
+        | Value::MultiLineString(_)
+        | Value::Point(_)
+        | Value::MultiPoint(_) => {
+            match geo::Geometry::<f64>::try_from(geom.clone()) {
+                Ok(feature) => feature.intersects(loe_polygon),
+                Err(_) => false,
             }
         }
         _ => {
-            println!("Non Polygon GeoJSON not supported");
-            return false;
+            println!("GeometryCollection GeoJSON not supported");
+            false
         }
     }
 }